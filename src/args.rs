@@ -1,20 +1,74 @@
-use clap::Parser;
+use std::{path::PathBuf, time::Duration};
+
+use clap::{Parser, Subcommand};
+
+use crate::{exit, forge::ForgeKind};
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, after_help = exit::HELP)]
 pub struct Args {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
     /// Also open the pr in a browser
     #[clap(long)]
     open: bool,
 
-    /// Create the pr as a draft
+    /// Create the pr ready for review instead of as a draft
     #[clap(long)]
-    no_draft: bool,
+    pub no_draft: bool,
 
     /// Don't create the pr if it doesn't exist yet
     #[clap(long)]
-    no_create: bool,
+    pub no_create: bool,
+
+    /// Open the pr without reviewing its title and body in an editor first
+    #[clap(long)]
+    pub no_edit: bool,
+
+    /// Request a review from a user, or an org/team, when creating the pr [default: pr.reviewer]
+    #[clap(long = "reviewer", value_name = "LOGIN", use_value_delimiter = true)]
+    pub reviewers: Vec<String>,
+
+    /// Also request a review from the CODEOWNERS of the changed files when creating the pr
+    /// [default: pr.requestOwners]
+    #[clap(long)]
+    pub request_owners: bool,
+
+    /// Assign the pr to a user when creating it [default: pr.assignee]
+    #[clap(long = "assignee", value_name = "LOGIN", use_value_delimiter = true)]
+    pub assignees: Vec<String>,
+
+    /// Label the pr when creating it [default: pr.label]
+    #[clap(long = "label", value_name = "NAME", use_value_delimiter = true)]
+    pub labels: Vec<String>,
+
+    /// Add the pr to a milestone, by title, when creating it [default: pr.milestone]
+    #[clap(long, value_name = "TITLE")]
+    pub milestone: Option<String>,
+
+    /// Which pr template to start the body from, by file name, when the repo has several
+    #[clap(long, value_name = "NAME")]
+    pub template: Option<String>,
+
+    /// Push the branch even when it has diverged from its pr, e.g. after a rebase, as long as the
+    /// remote branch is still where it was last seen
+    #[clap(long)]
+    pub force_with_lease: bool,
+
+    /// Print what would be pushed, created or merged instead of doing it
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Stop watching as soon as a check fails, only a required one when there are any
+    /// [required checks: pr.requiredCheck]
+    #[clap(long)]
+    pub fail_fast: bool,
+
+    /// Stop watching after this long, e.g. 10m or 1h30m, and list the checks still pending
+    #[clap(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+    pub timeout: Option<Duration>,
 
     /// Watch the output
     #[clap(long, default_value_t = 1)]
@@ -24,7 +78,46 @@ pub struct Args {
     #[clap(long)]
     pub branch: Option<String>,
 
+    /// Show pr number N instead of the current branch's
+    #[clap(long, value_name = "N", conflicts_with_all = &["branch", "url", "sha"])]
+    pub pr: Option<u32>,
+
+    /// Show the pr at this web url instead of the current branch's, it has to be on the upstream
+    /// remote's repo
+    #[clap(long, conflicts_with_all = &["branch", "sha"])]
+    pub url: Option<String>,
+
+    /// Show the open pr with this commit instead of the current branch's
+    #[clap(long, value_name = "OID", conflicts_with = "branch")]
+    pub sha: Option<String>,
+
+    /// Work on the repository at this path instead of the one the current directory is in
+    #[clap(long, value_name = "PATH")]
+    pub repo: Option<PathBuf>,
+
+    /// Which backend to fetch and create prs with
+    #[clap(long, value_enum, default_value_t = ForgeKind::Auto)]
+    pub forge: ForgeKind,
+
+    /// Override the api endpoint of the backend, e.g. to point at a local server
+    #[clap(long)]
+    pub api_url: Option<String>,
+
+    /// Serve the pr from a json fixture (a built in name or a path) instead of a forge
+    #[clap(long)]
+    pub fixture: Option<String>,
+
     // color
     #[clap(long, default_value_t = String::from("auto"))]
     color: String,
 }
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Suggest reviewers for the current branch, the people who last edited the lines it changes
+    Reviewers {
+        /// How many to suggest
+        #[clap(long, default_value_t = 5)]
+        limit: usize,
+    },
+}
@@ -0,0 +1,253 @@
+//! Suggesting reviewers from blame: whoever last edited the lines a pr changes knows them best.
+//!
+//! The lines are blamed as they are at the merge base, a changed or removed line counts for
+//! whoever wrote it and an added line for whoever wrote the lines either side of it.
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use git2::{BlameOptions, Delta, DiffOptions, Oid, Repository, Tree};
+use indicatif::HumanDuration;
+
+use crate::git_commands::commits_since;
+
+/// someone who last edited lines the pr touches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    pub name: String,
+    pub email: String,
+    /// how many of the touched lines they last edited
+    pub lines: usize,
+    /// when they last edited any of them, in seconds since the epoch
+    pub last_edit: i64,
+}
+
+impl Candidate {
+    /// their login, which only a github noreply address gives away
+    pub fn login(&self) -> Option<&str> {
+        login_from_email(&self.email)
+    }
+
+    /// their login when it is known, otherwise `name <email>`
+    pub fn who(&self) -> String {
+        match self.login() {
+            Some(login) => login.to_string(),
+            None => format!("{} <{}>", self.name, self.email),
+        }
+    }
+
+    /// who they are and what share of the `total` touched lines they last edited, and when
+    pub fn describe(&self, total: usize) -> String {
+        let share = self.lines * 100 / total.max(1);
+        let edited = UNIX_EPOCH + Duration::from_secs(self.last_edit.max(0) as u64);
+        let ago = SystemTime::now().duration_since(edited).unwrap_or_default();
+        format!(
+            "{} {share}% of {total} lines, last edited {} ago",
+            self.who(),
+            HumanDuration(ago)
+        )
+    }
+}
+
+/// the emails we commit with, `user.email` and whatever the pr's own commits were authored as
+fn our_emails(repo: &Repository, base: Oid) -> Result<Vec<String>, git2::Error> {
+    let mut emails = repo
+        .config()?
+        .get_string("user.email")
+        .into_iter()
+        .collect::<Vec<String>>();
+    for commit in commits_since(repo, base)? {
+        emails.extend(commit.author().email().map(String::from));
+    }
+    Ok(emails)
+}
+
+/// how many lines `path` has in `tree`, blame turns down a range past the last one
+fn line_count(repo: &Repository, tree: &Tree, path: &str) -> Result<usize, git2::Error> {
+    let blob = tree.get_path(Path::new(path))?.to_object(repo)?.peel_to_blob()?;
+    let content = blob.content();
+    let unterminated = !content.is_empty() && !content.ends_with(b"\n");
+    Ok(content.iter().filter(|byte| **byte == b'\n').count() + usize::from(unterminated))
+}
+
+/// the candidates for reviewing `base..HEAD`, and the number of lines it touches, the ones who
+/// edited the most of them first, then the ones who edited most recently, without us or bots
+pub fn suggest(repo: &Repository, base: Oid) -> Result<(Vec<Candidate>, usize), git2::Error> {
+    let ours = our_emails(repo, base)?;
+    let base_tree = repo.find_commit(base)?.tree()?;
+    let head_tree = repo.head()?.peel_to_tree()?;
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    let diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), Some(&mut options))?;
+
+    // the lines of each file at the base that the pr touches
+    let mut touched = BTreeMap::<String, Vec<usize>>::new();
+    diff.foreach(
+        &mut |_, _| true,
+        None,
+        Some(&mut |delta, hunk| {
+            let path = match (delta.status(), delta.old_file().path()) {
+                // a new file has nobody to ask about it
+                (Delta::Added, _) | (_, None) => return true,
+                (_, Some(path)) => path.to_string_lossy().into_owned(),
+            };
+            let start = hunk.old_start() as usize;
+            let lines = match hunk.old_lines() as usize {
+                // an insertion after line `start`, the lines around it are the context, which
+                // may be past either end of the file
+                0 => vec![start, start + 1],
+                count => (start..start + count).collect(),
+            };
+            touched.entry(path).or_default().extend(lines);
+            true
+        }),
+        None,
+    )?;
+
+    let mut by_email = BTreeMap::<String, Candidate>::new();
+    let mut total = 0;
+    for (path, lines) in touched {
+        let count = line_count(repo, &base_tree, &path)?;
+        let lines = lines
+            .into_iter()
+            .filter(|line| (1..=count).contains(line))
+            .collect::<Vec<usize>>();
+        let (Some(first), Some(last)) = (lines.iter().min(), lines.iter().max()) else {
+            continue
+        };
+        let mut options = BlameOptions::new();
+        options.newest_commit(base).min_line(*first).max_line(*last);
+        let blame = repo.blame_file(Path::new(&path), Some(&mut options))?;
+        for line in lines {
+            let Some(hunk) = blame.get_line(line) else {
+                continue
+            };
+            let signature = hunk.final_signature();
+            let name = signature.name().unwrap_or_default().to_string();
+            let email = signature.email().unwrap_or_default().to_string();
+            total += 1;
+            if is_bot(&name, &email) || ours.iter().any(|ours| ours.eq_ignore_ascii_case(&email)) {
+                continue
+            }
+            let when = signature.when().seconds();
+            let candidate = by_email
+                .entry(email.to_lowercase())
+                .or_insert_with(|| Candidate {
+                    name,
+                    email,
+                    lines: 0,
+                    last_edit: when,
+                });
+            candidate.lines += 1;
+            candidate.last_edit = candidate.last_edit.max(when);
+        }
+    }
+
+    let mut candidates = by_email.into_values().collect::<Vec<Candidate>>();
+    candidates.sort_by(|a, b| b.lines.cmp(&a.lines).then(b.last_edit.cmp(&a.last_edit)));
+    Ok((candidates, total))
+}
+
+/// the login in a github noreply address, `123+octocat@users.noreply.github.com` or the older
+/// `octocat@users.noreply.github.com`
+pub fn login_from_email(email: &str) -> Option<&str> {
+    let user = email.strip_suffix("@users.noreply.github.com")?;
+    let login = user.split_once('+').map_or(user, |(_, login)| login);
+    match login.is_empty() {
+        true => None,
+        false => Some(login),
+    }
+}
+
+/// whether a commit author is an automated account, e.g. `dependabot[bot]` or `renovate-bot`
+pub fn is_bot(name: &str, email: &str) -> bool {
+    let name = name.to_lowercase();
+    let user = email.split('@').next().unwrap_or_default().to_lowercase();
+    [name.as_str(), user.as_str()].iter().any(|who| {
+        who.ends_with("[bot]") || who.ends_with("-bot") || who.ends_with(" bot") || *who == "bot"
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_login_and_bots() {
+        assert_eq!(
+            login_from_email("583231+octocat@users.noreply.github.com"),
+            Some("octocat")
+        );
+        assert_eq!(
+            login_from_email("octocat@users.noreply.github.com"),
+            Some("octocat")
+        );
+        assert_eq!(login_from_email("octocat@github.com"), None);
+
+        assert!(is_bot(
+            "dependabot[bot]",
+            "49699333+dependabot[bot]@users.noreply.github.com"
+        ));
+        assert!(is_bot("Renovate Bot", "bot@renovateapp.com"));
+        assert!(is_bot("ci", "deploy-bot@example.com"));
+        assert!(!is_bot("Abbot Smith", "abbot@example.com"));
+    }
+
+    /// commit `files` as the whole tree on top of HEAD, as `author`
+    fn commit(repo: &Repository, author: &str, files: &[(&str, &str)]) -> Oid {
+        let signature = Signature::now(author, &format!("{author}@example.com")).unwrap();
+        let mut tree = repo.treebuilder(None).unwrap();
+        for (path, content) in files {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            tree.insert(path, blob, 0o100644).unwrap();
+        }
+        let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            author,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_suggest() {
+        let dir = std::env::temp_dir().join(format!("git-pr-blame-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("user.email", "alice@example.com")
+            .unwrap();
+        commit(&repo, "carol", &[("a.txt", "one\ntwo\n"), ("b.txt", "b\n")]);
+        let base = commit(
+            &repo,
+            "bob",
+            &[("a.txt", "one\ntwo\nthree\n"), ("b.txt", "b\n")],
+        );
+        // a changed first line, and lines added after the last, which has nothing below it
+        commit(
+            &repo,
+            "alice",
+            &[("a.txt", "uno\ntwo\nthree\nfour\n"), ("b.txt", "b\nc\n")],
+        );
+
+        let (candidates, total) = suggest(&repo, base).unwrap();
+        let candidates = candidates
+            .iter()
+            .map(|candidate| (candidate.name.as_str(), candidate.lines))
+            .collect::<Vec<_>>();
+        assert_eq!(candidates, vec![("carol", 2), ("bob", 1)]);
+        assert_eq!(total, 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
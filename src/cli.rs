@@ -1,7 +1,11 @@
 use std::{
     collections::HashMap,
-    error::Error,
-    sync::{Arc, Mutex},
+    fmt,
+    io::{stdin, stdout, IsTerminal},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     thread::sleep,
     time::{Duration, Instant, SystemTime},
 };
@@ -9,17 +13,103 @@ use std::{
 use clap::Parser;
 use colored::Colorize;
 use console::Emoji;
+use git2::{Oid, Repository};
 use indicatif::{HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
-use log::debug;
+use log::{debug, info, warn};
 
 use crate::{
-    args::Args,
-    git_commands::{current_branch_name, current_repo},
-    prinfo::PrInfo,
+    args::{Args, Command},
+    blame,
+    codeowners::{Approval, CodeOwners},
+    config,
+    error::Error,
+    exit,
+    forge::{self, DryRun, Fixture, Forge, ForgeError},
+    git_commands::{current_branch_name, current_repo, divergence, head_sha, pr_head, Divergence},
+    prinfo::{pr_base, push_branch, CheckOutcome, CreateOptions, PrInfo, StatusCheck},
+    remote::RemoteSpec,
 };
+/// which pr to show
+#[derive(Debug, Clone)]
+enum Selector {
+    /// the pr for the checked out branch, which is the one that can be created if it's missing
+    CurrentBranch(String),
+    /// the pr for a head, `branch` or `owner:branch`
+    Head(String),
+    Number(u32),
+    /// the open pr with this commit
+    Sha(String),
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selector::CurrentBranch(head) | Selector::Head(head) => write!(f, "{head}"),
+            Selector::Number(number) => write!(f, "#{number}"),
+            Selector::Sha(sha) => write!(f, "commit {sha}"),
+        }
+    }
+}
+
+impl Selector {
+    fn fetch(&self, forge: &dyn Forge) -> Result<Option<PrInfo>, ForgeError> {
+        match self {
+            Selector::CurrentBranch(head) | Selector::Head(head) => PrInfo::get(forge, head),
+            Selector::Number(number) => PrInfo::get_by_number(forge, *number),
+            Selector::Sha(sha) => PrInfo::get_by_sha(forge, sha),
+        }
+    }
+}
+
+/// why the watch stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    /// every check finished
+    Done,
+    /// the pr still had no checks once it was fetched again, the repo may not run any
+    NoChecks,
+    /// `--fail-fast` and a check failed
+    FailedFast,
+    /// `--timeout` ran out
+    TimedOut,
+    /// ctrl-c
+    Interrupted,
+}
+
+impl Stop {
+    /// why to stop watching `pr_info` now, if at all, a failed check only counts with
+    /// `fail_fast` and then only when it is one of the `required` ones if there are any, and no
+    /// checks only once `pr_info` was `refreshed` since the watch began
+    fn at(
+        pr_info: &PrInfo,
+        required: &[String],
+        fail_fast: bool,
+        refreshed: bool,
+        timed_out: bool,
+        interrupted: bool,
+    ) -> Option<Stop> {
+        if pr_info.is_complete() {
+            Some(Stop::Done)
+        } else if refreshed && pr_info.statusCheckRollup.is_empty() {
+            Some(Stop::NoChecks)
+        } else if fail_fast && !pr_info.failed_checks(required).is_empty() {
+            Some(Stop::FailedFast)
+        } else if timed_out {
+            Some(Stop::TimedOut)
+        } else if interrupted {
+            Some(Stop::Interrupted)
+        } else {
+            None
+        }
+    }
+}
+
 struct App {
     args: Args,
-    branch: String,
+    repo: Repository,
+    selector: Selector,
+    forge: Arc<dyn Forge>,
+    codeowners: Option<CodeOwners>,
     mp: MultiProgress,
     progress_bars: Arc<Mutex<HashMap<String, ProgressBar>>>,
 }
@@ -29,7 +119,6 @@ struct Pb {
     prefix: String,
     message: String,
     template: String,
-    tick_chars: String,
     indent: usize,
 }
 
@@ -66,10 +155,6 @@ impl Pb {
         self.template = template.into();
         self.clone()
     }
-    fn with_tick_chars<S: Into<String>>(&mut self, tick_chars: S) -> Self {
-        self.tick_chars = tick_chars.into();
-        self.clone()
-    }
     fn with_indent(&mut self, indent: usize) -> Self {
         self.indent = indent;
         self.clone()
@@ -90,23 +175,74 @@ impl Pb {
     }
 }
 
+/// the number of the pr `url` points at, which has to be on the upstream remote's repo when
+/// that's where the backend looks
+fn pr_number_from_url(repo: &Repository, url: &str, on_upstream: bool) -> Result<u32, Error> {
+    let (spec, number) = forge::pr_from_url(url).ok_or_else(|| {
+        Error::Parse(format!(
+            "a pr out of {url}, pass the pr's web url, e.g. https://github.com/org/repo/pull/123"
+        ))
+    })?;
+    let upstream = config::upstream_remote(repo);
+    match RemoteSpec::of(repo, &upstream) {
+        Some(upstream_spec) if on_upstream && !upstream_spec.same_repo(&spec) => {
+            Err(Error::Usage(format!(
+                "{url} is a pr on {}/{}, but the {upstream} remote is {}/{}, run git pr from a \
+                 clone of that repo",
+                spec.web_host(),
+                spec.path(),
+                upstream_spec.web_host(),
+                upstream_spec.path(),
+            )))
+        }
+        _ => Ok(number),
+    }
+}
+
 impl App {
-    fn new() -> Self {
+    fn new() -> Result<Self, Error> {
         let args = Args::parse();
-        let branch = match &args.branch {
-            Some(b) => b.to_string(),
-            None => {
-                let repo = current_repo();
-                current_branch_name(&repo).expect("must have a branch name")
-            }
+        let repo = current_repo(args.repo.as_deref())?;
+        let forge: Arc<dyn Forge> = match &args.fixture {
+            Some(fixture) => Arc::new(Fixture::load(fixture)?),
+            None => forge::select(args.forge, &repo, args.api_url.as_deref())?,
+        };
+        let forge: Arc<dyn Forge> = match args.dry_run {
+            true => Arc::new(DryRun::new(forge)),
+            false => forge,
+        };
+        debug!("using the {} backend", forge.name());
+        let selector = match (&args.pr, &args.url, &args.sha, &args.branch) {
+            (Some(number), ..) => Selector::Number(*number),
+            (_, Some(url), ..) => Selector::Number(pr_number_from_url(
+                &repo,
+                url,
+                args.fixture.is_none(),
+            )?),
+            (_, _, Some(sha), _) => Selector::Sha(sha.to_string()),
+            (_, _, _, Some(branch)) => Selector::Head(branch.to_string()),
+            _ => match current_branch_name(&repo) {
+                Some(branch) => Selector::CurrentBranch(pr_head(&repo, &branch)),
+                // a detached HEAD, e.g. a ci checkout, the pr is whichever one has HEAD in it
+                None => Selector::Sha(head_sha(&repo).ok_or_else(|| {
+                    Error::NotFound(
+                        "HEAD has no commits yet, commit something to open a pr for".to_string(),
+                    )
+                })?),
+            },
         };
 
-        Self {
+        let codeowners = repo.workdir().and_then(CodeOwners::load);
+
+        Ok(Self {
             args,
-            branch,
+            repo,
+            selector,
+            forge,
+            codeowners,
             mp: MultiProgress::new(),
             progress_bars: Arc::new(Mutex::new(HashMap::new())),
-        }
+        })
     }
 
     fn pb(&mut self, pb_args: &Pb) -> ProgressBar {
@@ -140,25 +276,28 @@ impl App {
             template = format!("{}{}", " ".repeat(pb_args.indent), template);
         }
 
-        let tick_chars = match pb_args.tick_chars.is_empty() {
-            true => "⣾⣽⣻⢿⡿⣟⣯⣷",
-            false => pb_args.tick_chars.as_str(),
-        };
-
         pb.set_style(
+            // a template the bar can't draw falls back to the plain one rather than panicking
             ProgressStyle::with_template(template.as_str())
-                .expect("ok")
-                .tick_chars(tick_chars),
+                .unwrap_or_else(|_| ProgressStyle::default_spinner())
+                .tick_chars("⣾⣽⣻⢿⡿⣟⣯⣷"),
         );
-        return progress_bars[&pb_args.key].clone()
+        progress_bars[&pb_args.key].clone()
     }
 
     fn get_progress_bars(&mut self, pr_info: &PrInfo) -> Vec<ProgressBar> {
         let mut pb_keys: Vec<Pb> = vec![];
 
         pb_keys.extend([
-            Pb::new_header("header")
-                .with_prefix(format!("#{} - {}", pr_info.number, pr_info.title)),
+            Pb::new_header("header").with_prefix(format!(
+                "#{} - {}{}",
+                pr_info.number,
+                pr_info.title,
+                match &pr_info.__refreshError {
+                    Some(e) => format!(" (stale, {e})").yellow().to_string(),
+                    None => String::new(),
+                }
+            )),
             Pb::new_with_pkey_and_message("url", &pr_info.url)
                 .with_template("> {msg}")
                 .with_indent(4),
@@ -175,10 +314,18 @@ impl App {
                 .unwrap()
                 .path
                 .len();
+            let codeowners = self.codeowners.clone().unwrap_or_default();
             pb_keys.extend(files.iter().map(|f| {
                 Pb::new_with_pkey_and_message(
                     f.path.as_str(),
-                    format!("{}{}{}{}", f.additions, "+".green(), f.deletions, "-".red()),
+                    format!(
+                        "{}{}{}{} {}",
+                        f.additions,
+                        "+".green(),
+                        f.deletions,
+                        "-".red(),
+                        codeowners.owners(&f.path).join(" ").dimmed()
+                    ),
                 )
                 .with_template(format!(
                     "  {} {{prefix:{longest_file}}} {} {{msg}}",
@@ -188,6 +335,28 @@ impl App {
             }));
         }
 
+        if let Some(codeowners) = &self.codeowners {
+            let paths = pr_info
+                .files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<&str>>();
+            let approvals = codeowners.approvals(&paths, &pr_info.approvers());
+            if !approvals.is_empty() {
+                pb_keys.push(Pb::new_section("owners"));
+                pb_keys.extend(approvals.into_iter().map(|(owner, approval)| {
+                    let approval = match approval {
+                        Approval::Approved => approval.to_string().green(),
+                        Approval::Covered => approval.to_string().dimmed(),
+                        Approval::Missing => approval.to_string().red(),
+                        Approval::Unknown => approval.to_string().yellow(),
+                    };
+                    Pb::new_with_pkey_and_message(format!("owner {owner}"), approval.to_string())
+                        .with_prefix(owner)
+                }));
+            }
+        }
+
         pb_keys.extend([
             Pb::new_section("details"),
             Pb::new_with_pkey_and_message("state", &pr_info.state),
@@ -198,6 +367,23 @@ impl App {
             Pb::new_with_pkey_and_message("sha", pr_info.sha()),
             Pb::new_with_pkey_and_message("url", pr_info.url.clone()),
         ]);
+        let labels = pr_info
+            .labels
+            .iter()
+            .map(|label| label.name.clone())
+            .collect::<Vec<String>>();
+        for (key, values) in [
+            ("reviewers", &pr_info.reviewRequests),
+            ("assignees", &pr_info.assignees),
+            ("labels", &labels),
+        ] {
+            if !values.is_empty() {
+                pb_keys.push(Pb::new_with_pkey_and_message(key, values.join(", ")));
+            }
+        }
+        if let Some(milestone) = &pr_info.milestone {
+            pb_keys.push(Pb::new_with_pkey_and_message("milestone", milestone));
+        }
 
         if !pr_info.statusCheckRollup.is_empty() {
             pb_keys.push(Pb::new_section("checks"));
@@ -211,54 +397,351 @@ impl App {
             }));
         }
 
-        let pbs = pb_keys
+        pb_keys
             .iter()
             .map(|pb_args| self.pb(pb_args))
-            .collect::<Vec<ProgressBar>>();
-        return pbs
+            .collect::<Vec<ProgressBar>>()
+    }
+
+    /// open a pr for the current branch, a draft unless `--no-draft`, reviewed in an editor unless
+    /// `--no-edit`
+    fn create(&self) -> Result<PrInfo, Error> {
+        let head = match &self.selector {
+            Selector::CurrentBranch(head) => head,
+            selector => return Err(Error::NoPr(format!("no pr found for {selector}"))),
+        };
+        if self.args.no_create {
+            return Err(Error::NoPr(format!(
+                "no pr for {head}, run without --no-create to open one"
+            )))
+        }
+        info!("no pr for {head}, creating one");
+        let options = CreateOptions {
+            draft: !self.args.no_draft,
+            template: self.args.template.clone(),
+            // there is only an editor to open when someone is there to use it
+            edit: !self.args.no_edit && stdin().is_terminal(),
+            dry_run: self.args.dry_run,
+            force_with_lease: self.args.force_with_lease,
+            // what is given on the command line replaces what is in git config
+            reviewers: or_config(&self.args.reviewers, || config::reviewers(&self.repo)),
+            request_owners: self.args.request_owners || config::request_owners(&self.repo),
+            assignees: or_config(&self.args.assignees, || config::assignees(&self.repo)),
+            labels: or_config(&self.args.labels, || config::labels(&self.repo)),
+            milestone: self
+                .args
+                .milestone
+                .clone()
+                .or_else(|| config::milestone(&self.repo)),
+        };
+        let progress = self.push_progress();
+        let created = PrInfo::create(&self.repo, self.forge.as_ref(), &options, &progress);
+        progress.finish_and_clear();
+        created
+    }
+
+    /// a bar for the transfer of a push
+    fn push_progress(&self) -> ProgressBar {
+        let progress = self.mp.add(ProgressBar::new(0));
+        progress.set_style(
+            ProgressStyle::with_template(&format!(
+                "{} {{prefix:10.white}} {} {{bar:30}} {{msg}}",
+                "---->".magenta(),
+                "-->".magenta()
+            ))
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+        progress.set_prefix("push");
+        progress
+    }
+
+    /// compare the checked out branch with the head of its pr, and with `--force-with-lease`
+    /// push it when the pr is missing its commits
+    fn sync(&self, pr_info: &PrInfo) -> Result<(), Error> {
+        let (Some(branch), Some(local)) = (
+            current_branch_name(&self.repo),
+            self.repo.head().ok().and_then(|head| head.target()),
+        ) else {
+            return Ok(())
+        };
+        let Ok(remote) = Oid::from_str(&pr_info.headRefOid) else {
+            return Ok(())
+        };
+        let divergence = divergence(&self.repo, local, remote);
+        let short = &pr_info.headRefOid[..pr_info.headRefOid.len().min(7)];
+        let status = format!("{branch} is {divergence} its pr at {short}");
+        match divergence {
+            Divergence::UpToDate => debug!("{status}"),
+            Divergence::Behind(_) | Divergence::Unknown => warn!("{status}"),
+            Divergence::Ahead(_) | Divergence::Diverged { .. } if self.args.force_with_lease => {
+                info!("{status}");
+                let progress = self.push_progress();
+                let pushed = push_branch(
+                    &self.repo,
+                    &branch,
+                    Some(remote),
+                    self.args.dry_run,
+                    &progress,
+                );
+                progress.finish_and_clear();
+                pushed?;
+            }
+            Divergence::Ahead(_) | Divergence::Diverged { .. } => {
+                warn!("{status}, pass --force-with-lease to push it")
+            }
+        }
+        Ok(())
     }
 
-    async fn run_loop(&mut self) {
-        let start = SystemTime::now();
-        let pr_info = Arc::new(Mutex::new(
-            PrInfo::get(self.branch.clone()).expect("must have pr info"),
-        ));
+    /// print the `limit` people blame suggests reviewing the current branch, against the base of
+    /// its pr when it has one
+    fn reviewers(&self, limit: usize) -> Result<(), Error> {
+        let pr_info = self.selector.fetch(self.forge.as_ref()).unwrap_or_else(|e| {
+            warn!("could not fetch the pr, using the default branch as its base: {e}");
+            None
+        });
+        let (base, merge_base) = pr_base(&self.repo, pr_info.as_ref(), self.forge.as_ref())?;
+        let (candidates, total) = blame::suggest(&self.repo, merge_base)?;
+        if candidates.is_empty() {
+            info!("nobody else has edited the lines changed since {base}");
+        }
+        for candidate in candidates.iter().take(limit) {
+            println!("{}", candidate.describe(total));
+        }
+        Ok(())
+    }
 
-        loop {
-            let pr_info = pr_info.clone();
-            if pr_info.lock().unwrap().is_complete() {
-                break
+    /// show the pr until its checks are done, or `--fail-fast` or `--timeout` stop it early, the
+    /// exit code is how the checks turned out
+    async fn run_loop(&mut self) -> Result<i32, Error> {
+        let pr_info = match self.selector.fetch(self.forge.as_ref())? {
+            Some(pr_info) => {
+                if let Selector::CurrentBranch(_) = self.selector {
+                    self.sync(&pr_info)?;
+                }
+                pr_info
             }
+            None => {
+                let pr_info = self.create()?;
+                // the plan is all there is, the pr it would open has nothing to watch
+                if self.args.dry_run {
+                    return Ok(exit::SUCCESS)
+                }
+                pr_info
+            }
+        };
+        let pr_info = Arc::new(Mutex::new(pr_info));
 
-            self.get_progress_bars(&pr_info.lock().unwrap())
-                .iter()
-                .for_each(|pb| {
+        // ctrl-c stops watching rather than the process, so the exit code can say so
+        let interrupted = Arc::new(AtomicBool::new(false));
+        {
+            let interrupted = interrupted.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    interrupted.store(true, Ordering::SeqCst);
+                }
+            });
+        }
+
+        let required = config::required_checks(&self.repo);
+        let watching = Instant::now();
+        let watched_from = SystemTime::now();
+        // one refresh at a time, however long the forge takes to answer
+        let refreshing = Arc::new(AtomicBool::new(false));
+        let stop = loop {
+            let stop = {
+                let pr_info = pr_info.lock().unwrap();
+                // a failed refresh keeps what was fetched before, it only counts once it worked
+                let refreshed = pr_info.__refreshError.is_none()
+                    && pr_info.__createdAt.is_some_and(|at| at > watched_from);
+                Stop::at(
+                    &pr_info,
+                    &required,
+                    self.args.fail_fast,
+                    refreshed,
+                    self.args
+                        .timeout
+                        .is_some_and(|timeout| watching.elapsed() >= timeout),
+                    interrupted.load(Ordering::SeqCst),
+                )
+            };
+            if let Some(stop) = stop {
+                break stop
+            }
+
+            let (stale, number) = {
+                let pr_info = pr_info.lock().unwrap();
+                self.get_progress_bars(&pr_info).iter().for_each(|pb| {
                     pb.inc(1);
                 });
+                (pr_info.is_stale(), pr_info.number)
+            };
 
-            tokio::spawn(async move {
-                pr_info.lock().unwrap().update();
-            });
+            if stale && !refreshing.swap(true, Ordering::SeqCst) {
+                let pr_info = pr_info.clone();
+                let forge = self.forge.clone();
+                let refreshing = refreshing.clone();
+                tokio::task::spawn_blocking(move || {
+                    // the lock is only taken to swap in what was fetched, so the bars keep
+                    // drawing what was last fetched, marked stale if a refresh fails
+                    let fetched = PrInfo::refetch(forge.as_ref(), number);
+                    if let Err(e) = pr_info.lock().unwrap().update(fetched) {
+                        debug!("could not refresh the pr: {e}");
+                    }
+                    refreshing.store(false, Ordering::SeqCst);
+                });
+            }
 
             sleep(Duration::from_millis(75));
-        }
+        };
 
         self.get_progress_bars(&pr_info.lock().unwrap())
             .iter()
             .for_each(|pb| {
                 pb.finish();
             });
+        let pr_info = pr_info.lock().unwrap();
+        // the bars only draw on a terminal, a pipe gets the report instead
+        if !stdout().is_terminal() {
+            print!("{}", pr_info.to_string());
+        }
+        let names = |checks: Vec<&StatusCheck>| {
+            checks
+                .iter()
+                .map(|check| check.name())
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let pending = || match pr_info.statusCheckRollup.is_empty() {
+            true => "no checks have shown up yet".to_string(),
+            false => format!("still pending: {}", names(pr_info.pending_checks())),
+        };
+        match stop {
+            Stop::Done => {}
+            Stop::NoChecks => {
+                warn!("no checks showed up, the repo may not run any");
+                return Ok(exit::NO_CHECKS)
+            }
+            Stop::FailedFast => warn!(
+                "stopped early as {} failed",
+                names(pr_info.failed_checks(&required))
+            ),
+            Stop::TimedOut => warn!(
+                "timed out after {}, {}",
+                HumanDuration(watching.elapsed()),
+                pending()
+            ),
+            Stop::Interrupted => warn!("interrupted, {}", pending()),
+        }
+        let outcome = pr_info.outcome();
+        match outcome {
+            CheckOutcome::Passed => info!("{outcome}"),
+            _ => warn!("{outcome}"),
+        }
+        Ok(exit::of_checks(outcome))
+    }
+}
+
+/// `values` from the command line, or the ones in git config when there are none
+fn or_config(values: &[String], config: impl Fn() -> Vec<String>) -> Vec<String> {
+    match values.is_empty() {
+        true => config(),
+        false => values.to_vec(),
     }
 }
 
 static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", ":-)");
 
-pub(crate) async fn main() -> Result<(), Box<dyn Error>> {
+/// the exit code, see `exit`
+pub(crate) async fn main() -> Result<i32, Error> {
     let started = Instant::now();
-    let mut app = App::new();
+    let mut app = App::new()?;
 
-    app.run_loop().await;
+    if let Some(Command::Reviewers { limit }) = app.args.command {
+        app.reviewers(limit)?;
+        return Ok(exit::SUCCESS)
+    }
+    let code = app.run_loop().await?;
 
     println!("{} Done in {}", SPARKLE, HumanDuration(started.elapsed()));
-    Ok(())
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::prinfo::{CheckConclusionState, CheckStatusState};
+
+    #[test]
+    fn test_pr_number_from_url_on_upstream() {
+        let dir = std::env::temp_dir().join(format!("git-pr-url-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        repo.remote("origin", "git@github.com:octo/uploader.git")
+            .unwrap();
+
+        let number = pr_number_from_url(&repo, "https://github.com/Octo/uploader/pull/42", true);
+        assert_eq!(number.unwrap(), 42);
+        let fork = pr_number_from_url(&repo, "https://github.com/fork/uploader/pull/42", true);
+        assert!(matches!(fork, Err(Error::Usage(_))), "{fork:?}");
+        let other_host =
+            pr_number_from_url(&repo, "https://ghe.example.com/octo/uploader/pull/42", true);
+        assert!(matches!(other_host, Err(Error::Usage(_))), "{other_host:?}");
+        // a fixture isn't on any remote
+        let fixture = pr_number_from_url(&repo, "https://github.com/fork/uploader/pull/42", false);
+        assert_eq!(fixture.unwrap(), 42);
+        let issue = pr_number_from_url(&repo, "https://github.com/octo/uploader/issues/42", true);
+        assert!(matches!(issue, Err(Error::Parse(_))), "{issue:?}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_stop() {
+        let check = |name: &str, conclusion: Option<CheckConclusionState>| StatusCheck::CheckRun {
+            completedAt: String::new(),
+            status: match &conclusion {
+                Some(_) => CheckStatusState::Completed,
+                None => CheckStatusState::InProgress,
+            },
+            conclusion,
+            detailsUrl: String::new(),
+            name: name.to_string(),
+            startedAt: String::new(),
+            workflowName: String::new(),
+        };
+        let pr_info = |checks: Vec<StatusCheck>| PrInfo {
+            statusCheckRollup: checks,
+            ..Default::default()
+        };
+        let failing = pr_info(vec![
+            check("lint", Some(CheckConclusionState::Failure)),
+            check("test", None),
+        ]);
+        let none = &[];
+        let test = &["test".to_string()];
+        let lint = &["lint".to_string()];
+
+        assert_eq!(Stop::at(&failing, none, false, false, false, false), None);
+        assert_eq!(Stop::at(&failing, none, true, false, false, false), Some(Stop::FailedFast));
+        assert_eq!(Stop::at(&failing, lint, true, false, false, false), Some(Stop::FailedFast));
+        // only a required check failing stops it early
+        assert_eq!(Stop::at(&failing, test, true, true, false, false), None);
+        assert_eq!(Stop::at(&failing, test, true, false, true, false), Some(Stop::TimedOut));
+        assert_eq!(Stop::at(&failing, test, true, false, false, true), Some(Stop::Interrupted));
+
+        // no checks yet is still pending, until the pr is fetched again and there still are none
+        let no_checks = pr_info(vec![]);
+        assert_eq!(Stop::at(&no_checks, none, true, false, false, false), None);
+        assert_eq!(Stop::at(&no_checks, none, true, false, true, false), Some(Stop::TimedOut));
+        assert_eq!(Stop::at(&no_checks, none, true, true, false, false), Some(Stop::NoChecks));
+        assert_eq!(Stop::at(&no_checks, none, true, true, true, true), Some(Stop::NoChecks));
+
+        let done = pr_info(vec![
+            check("lint", Some(CheckConclusionState::Failure)),
+            check("test", Some(CheckConclusionState::Success)),
+        ]);
+        // finishing wins over the timeout and ctrl-c that came with it
+        assert_eq!(Stop::at(&done, none, true, false, true, true), Some(Stop::Done));
+    }
 }
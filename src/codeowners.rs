@@ -0,0 +1,286 @@
+//! CODEOWNERS files, which say who has to review changes to which paths.
+//!
+//! Patterns follow github's take on gitignore: `*` and `?` stay within a directory, `**` crosses
+//! them, a pattern with a slash anywhere but its end is relative to the repo root, and a pattern
+//! that names a directory owns everything under it. The last pattern that matches a path wins.
+use std::{fmt, fs, path::Path};
+
+/// where a repo keeps its CODEOWNERS, the first one found is used
+const LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    /// the pattern split on `/`, with a leading `**` when it may match at any depth
+    segments: Vec<String>,
+    /// ends in a `/`, so only the contents of matching directories are owned
+    dir_only: bool,
+    /// `@user`, `@org/team` or an email address, none means the paths have no owners
+    owners: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+/// whether `name` matches the single segment `pattern`, `*` is any run of characters and `?` any
+/// one character
+fn matches_segment(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            matches_segment(&pattern[1..], name)
+                || (!name.is_empty() && matches_segment(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => matches_segment(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => matches_segment(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// whether the segments of `path` match the segments of `pattern`, `**` is any number of segments
+fn matches_segments(pattern: &[String], path: &[&str]) -> bool {
+    match (pattern.first(), path.first()) {
+        (None, None) => true,
+        (Some(p), _) if p == "**" => {
+            matches_segments(&pattern[1..], path)
+                || (!path.is_empty() && matches_segments(pattern, &path[1..]))
+        }
+        (Some(p), Some(name)) => {
+            let p = p.chars().collect::<Vec<char>>();
+            let name = name.chars().collect::<Vec<char>>();
+            matches_segment(&p, &name) && matches_segments(&pattern[1..], &path[1..])
+        }
+        _ => false,
+    }
+}
+
+impl Rule {
+    fn parse(pattern: &str, owners: Vec<String>) -> Self {
+        let dir_only = pattern.ends_with('/');
+        let trimmed = pattern.trim_end_matches('/');
+        // a slash at the start or in the middle ties the pattern to the root
+        let anchored = trimmed.contains('/');
+        let mut segments = trimmed
+            .trim_start_matches('/')
+            .split('/')
+            .map(String::from)
+            .collect::<Vec<String>>();
+        if !anchored {
+            segments.insert(0, "**".to_string());
+        }
+        Self {
+            segments,
+            dir_only,
+            owners,
+        }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let path = path.split('/').collect::<Vec<&str>>();
+        // `dir/*` only owns what is directly in `dir`, not what is further down
+        let whole_dirs = self.segments.last().is_none_or(|last| last != "*");
+        let matches_file = !self.dir_only && matches_segments(&self.segments, &path);
+        let matches_dir =
+            whole_dirs && (1..path.len()).any(|len| matches_segments(&self.segments, &path[..len]));
+        matches_file || matches_dir
+    }
+}
+
+impl CodeOwners {
+    pub fn parse(text: &str) -> Self {
+        let rules = text
+            .lines()
+            .map(|line| line.split_once(" #").map_or(line, |(rule, _)| rule).trim())
+            // comments, and gitlab's `[Section]` headers
+            .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('['))
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let pattern = words.next()?;
+                Some(Rule::parse(pattern, words.map(String::from).collect()))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// the CODEOWNERS of the repo checked out at `workdir`, if it has one
+    pub fn load(workdir: &Path) -> Option<Self> {
+        LOCATIONS
+            .iter()
+            .find_map(|location| fs::read_to_string(workdir.join(location)).ok())
+            .map(|text| Self::parse(&text))
+    }
+
+    /// the owners of `path`, from the last rule that matches it
+    pub fn owners(&self, path: &str) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path))
+            .map_or(&[], |rule| &rule.owners)
+    }
+
+    /// the owners of any of `paths`, each once, in the order they first come up
+    pub fn owners_of<'a>(&self, paths: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+        let mut owners: Vec<String> = vec![];
+        for path in paths {
+            for owner in self.owners(path) {
+                if !owners.contains(owner) {
+                    owners.push(owner.clone());
+                }
+            }
+        }
+        owners
+    }
+
+    /// where each owner of `paths` stands given the logins that approved, a team's approval can't
+    /// be told from its members' so a file a team owns is only known to be approved when another
+    /// of its owners has
+    pub fn approvals(&self, paths: &[&str], approvers: &[String]) -> Vec<(String, Approval)> {
+        let approved =
+            |owner: &str| reviewer(owner).is_some_and(|login| approvers.iter().any(|a| a == login));
+        let is_team = |owner: &String| reviewer(owner).is_some_and(|login| login.contains('/'));
+        // the files no owner is known to have approved, and whether a team owns them
+        let unapproved = paths
+            .iter()
+            .filter(|path| !self.owners(path).iter().any(|owner| approved(owner)))
+            .map(|path| (*path, self.owners(path).iter().any(is_team)))
+            .collect::<Vec<(&str, bool)>>();
+        self.owners_of(paths.iter().copied())
+            .into_iter()
+            .map(|owner| {
+                let mut owned = unapproved
+                    .iter()
+                    .filter(|(path, _)| self.owners(path).contains(&owner));
+                let approval = match approved(&owner) {
+                    true => Approval::Approved,
+                    false if owned.clone().any(|(_, team)| !team) => Approval::Missing,
+                    false if owned.next().is_some() => Approval::Unknown,
+                    false => Approval::Covered,
+                };
+                (owner, approval)
+            })
+            .collect()
+    }
+}
+
+/// where an owner's review of a pr stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Approved,
+    /// the owner hasn't approved, but every file they own has been by another of its owners
+    Covered,
+    /// some file the owner owns has no approval from any of its owners yet
+    Missing,
+    /// the files the owner owns that aren't approved yet are owned by a team, which may have
+    Unknown,
+}
+
+impl fmt::Display for Approval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Approval::Approved => write!(f, "approved"),
+            Approval::Covered => write!(f, "covered by another owner"),
+            Approval::Missing => write!(f, "approval missing"),
+            Approval::Unknown => write!(f, "team approval can't be determined"),
+        }
+    }
+}
+
+/// an owner as a forge takes it for a review request, `@user` is `user` and `@org/team` is
+/// `org/team`, owners given by email have no login to request
+pub fn reviewer(owner: &str) -> Option<&str> {
+    owner.strip_prefix('@')
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_owners() {
+        // the examples from github's documentation
+        let codeowners = CodeOwners::parse(
+            "
+            # comment
+            *       @global-owner1 @global-owner2
+            *.js    @js-owner #This is an inline comment.
+            *.go docs@example.com
+            *.txt @octo-org/octocats
+            /build/logs/ @doctocat
+            docs/*  docs@example.com
+            apps/ @octocat
+            /docs/ @doctocat
+            /scripts/ @doctocat @octocat
+            **/logs @octocat
+            /apps/ @octocat
+            /apps/github
+            ",
+        );
+        for (path, owners) in [
+            ("README.md", vec!["@global-owner1", "@global-owner2"]),
+            ("src/app.js", vec!["@js-owner"]),
+            ("main.go", vec!["docs@example.com"]),
+            ("notes/a.txt", vec!["@octo-org/octocats"]),
+            ("build/logs/x/y.log", vec!["@octocat"]),
+            ("docs/getting-started.md", vec!["@doctocat"]),
+            ("docs/build-app/troubleshooting.md", vec!["@doctocat"]),
+            (
+                "src/docs/getting-started.md",
+                vec!["@global-owner1", "@global-owner2"],
+            ),
+            ("scripts/run.sh", vec!["@doctocat", "@octocat"]),
+            ("deeply/nested/logs/today.txt", vec!["@octocat"]),
+            ("apps/web/index.html", vec!["@octocat"]),
+            ("apps/github/index.html", vec![]),
+        ] {
+            assert_eq!(codeowners.owners(path), owners, "{path}");
+        }
+        let docs = CodeOwners::parse("* @a\ndocs/* @b");
+        assert_eq!(docs.owners("docs/getting-started.md"), ["@b"]);
+        assert_eq!(docs.owners("docs/build-app/troubleshooting.md"), ["@a"]);
+        assert_eq!(
+            codeowners.approvals(
+                &["scripts/run.sh", "build/logs/today.log", "README.md"],
+                &["octocat".to_string()]
+            ),
+            vec![
+                ("@doctocat".to_string(), Approval::Covered),
+                ("@octocat".to_string(), Approval::Approved),
+                ("@global-owner1".to_string(), Approval::Missing),
+                ("@global-owner2".to_string(), Approval::Missing),
+            ]
+        );
+        assert_eq!(
+            codeowners.owners_of(["scripts/run.sh", "apps/x", "README.md"]),
+            vec!["@doctocat", "@octocat", "@global-owner1", "@global-owner2"]
+        );
+    }
+
+    #[test]
+    fn test_team_approvals() {
+        let codeowners =
+            CodeOwners::parse("* @alice\n*.txt @octo-org/octocats @bob\n*.md @octo-org/docs");
+        let paths = ["a.txt", "b.md", "c.rs"];
+        assert_eq!(
+            codeowners.approvals(&paths, &["alice".to_string()]),
+            vec![
+                ("@octo-org/octocats".to_string(), Approval::Unknown),
+                ("@bob".to_string(), Approval::Unknown),
+                ("@octo-org/docs".to_string(), Approval::Unknown),
+                ("@alice".to_string(), Approval::Approved),
+            ]
+        );
+        assert_eq!(
+            codeowners.approvals(&paths, &["bob".to_string()]),
+            vec![
+                ("@octo-org/octocats".to_string(), Approval::Covered),
+                ("@bob".to_string(), Approval::Approved),
+                ("@octo-org/docs".to_string(), Approval::Unknown),
+                ("@alice".to_string(), Approval::Missing),
+            ]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Settings read from git config, all under the `pr` section, e.g. `git config pr.forge gitlab`.
+use clap::ValueEnum;
+use git2::Repository;
+
+use crate::forge::ForgeKind;
+
+fn get_string(repo: &Repository, key: &str) -> Option<String> {
+    repo.config().ok()?.get_string(key).ok()
+}
+
+fn get_bool(repo: &Repository, key: &str) -> Option<bool> {
+    repo.config().ok()?.get_bool(key).ok()
+}
+
+/// every value of a key that can be given more than once, each of which can be a comma separated
+/// list
+fn get_list(repo: &Repository, key: &str) -> Vec<String> {
+    let Ok(config) = repo.config() else {
+        return vec![]
+    };
+    let Ok(entries) = config.multivar(key, None) else {
+        return vec![]
+    };
+    let mut values = vec![];
+    for entry in &entries {
+        let Ok(entry) = entry else { continue };
+        values.extend(
+            entry
+                .value()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(String::from),
+        );
+    }
+    values
+}
+
+/// the backend configured for `host` with `pr.<host>.forge`, or for every host with `pr.forge`
+pub fn forge(repo: &Repository, host: &str) -> Option<ForgeKind> {
+    let kind =
+        get_string(repo, &format!("pr.{host}.forge")).or_else(|| get_string(repo, "pr.forge"))?;
+    ForgeKind::from_str(&kind, true).ok()
+}
+
+/// the branch prs are based on, `pr.base`, when it shouldn't be detected
+pub fn base_branch(repo: &Repository) -> Option<String> {
+    get_string(repo, "pr.base")
+}
+
+/// the remote prs are opened against, `pr.upstreamRemote`, `origin` by default
+pub fn upstream_remote(repo: &Repository) -> String {
+    get_string(repo, "pr.upstreamRemote").unwrap_or_else(|| "origin".to_string())
+}
+
+/// the remote branches are pushed to, `pr.pushRemote`, then git's own `remote.pushDefault`, then
+/// the upstream remote, point it at your fork when working from one
+pub fn push_remote(repo: &Repository) -> String {
+    get_string(repo, "pr.pushRemote")
+        .or_else(|| get_string(repo, "remote.pushDefault"))
+        .unwrap_or_else(|| upstream_remote(repo))
+}
+
+/// how to title a pr of several commits, `pr.title`: `branch` (the default) for the branch name,
+/// `first` or `last` for that commit's subject
+pub fn multi_commit_title(repo: &Repository) -> String {
+    get_string(repo, "pr.title").unwrap_or_else(|| "branch".to_string())
+}
+
+/// who to request a review from on every new pr, `pr.reviewer`, users or `org/team` teams
+pub fn reviewers(repo: &Repository) -> Vec<String> {
+    get_list(repo, "pr.reviewer")
+}
+
+/// who to assign every new pr to, `pr.assignee`
+pub fn assignees(repo: &Repository) -> Vec<String> {
+    get_list(repo, "pr.assignee")
+}
+
+/// the labels to put on every new pr, `pr.label`
+pub fn labels(repo: &Repository) -> Vec<String> {
+    get_list(repo, "pr.label")
+}
+
+/// the milestone to put new prs in, `pr.milestone`
+pub fn milestone(repo: &Repository) -> Option<String> {
+    get_string(repo, "pr.milestone")
+}
+
+/// whether to request reviews from the CODEOWNERS of what a new pr changes, `pr.requestOwners`
+pub fn request_owners(repo: &Repository) -> bool {
+    get_bool(repo, "pr.requestOwners").unwrap_or(false)
+}
+
+/// the checks that have to pass, by name, `pr.requiredCheck`, only these stop `--fail-fast`
+pub fn required_checks(repo: &Repository) -> Vec<String> {
+    get_list(repo, "pr.requiredCheck")
+}
@@ -0,0 +1,80 @@
+//! The errors that make it up to `main`, each worded so it says what to do about it.
+use std::fmt;
+
+use crate::{forge::ForgeError, git_commands::RepoError, prinfo::TemplateError};
+
+#[derive(Debug)]
+pub enum Error {
+    /// a git operation on the local repository failed
+    Git(git2::Error),
+    /// there is no repository to work on
+    Repo(RepoError),
+    /// the backend failed or turned the request down
+    Forge(ForgeError),
+    /// there are no usable credentials for the backend, or it rejected them
+    Auth(String),
+    /// the arguments don't go together, or with the repo
+    Usage(String),
+    /// something we were given, or got back, couldn't be made sense of
+    Parse(String),
+    /// there is no pr to show, and none was created
+    NoPr(String),
+    /// something else we need isn't there, e.g. the branch to open a pr against
+    NotFound(String),
+    /// pushing the branch failed, the first part is what was pushed where
+    Push(String, git2::Error),
+    /// picking a pr template, or editing the pr message, failed
+    Template(TemplateError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Git(e) => write!(f, "git: {}", e.message()),
+            Error::Repo(e) => write!(f, "{e}"),
+            Error::Forge(e) => write!(f, "{e}"),
+            Error::Auth(message) => write!(f, "not authenticated: {message}"),
+            Error::Parse(message) => write!(f, "could not parse {message}"),
+            Error::Usage(message) | Error::NoPr(message) | Error::NotFound(message) => {
+                write!(f, "{message}")
+            }
+            Error::Push(what, e) => write!(
+                f,
+                "could not push {what}: {}, check the remote is reachable and that you can push \
+                 to it",
+                e.message()
+            ),
+            Error::Template(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Self {
+        Error::Git(e)
+    }
+}
+
+impl From<RepoError> for Error {
+    fn from(e: RepoError) -> Self {
+        Error::Repo(e)
+    }
+}
+
+impl From<ForgeError> for Error {
+    fn from(e: ForgeError) -> Self {
+        match e {
+            ForgeError::Auth(message) => Error::Auth(message),
+            ForgeError::Parse(e) => Error::Parse(format!("the backend's response: {e}")),
+            e => Error::Forge(e),
+        }
+    }
+}
+
+impl From<TemplateError> for Error {
+    fn from(e: TemplateError) -> Self {
+        Error::Template(e)
+    }
+}
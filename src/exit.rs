@@ -0,0 +1,90 @@
+//! The exit codes, so `git pr` can gate a script, e.g. `git pr && ./deploy.sh`.
+use indoc::indoc;
+
+use crate::{error::Error, prinfo::CheckOutcome};
+
+/// every check passed
+pub const SUCCESS: i32 = 0;
+/// something went wrong that has no code of its own, e.g. in git, a push or the editor
+pub const ERROR: i32 = 1;
+/// the arguments didn't make sense, this is the code clap exits with
+pub const USAGE: i32 = 2;
+/// at least one check failed
+pub const CHECKS_FAILED: i32 = 3;
+/// a check was cancelled or timed out, or the watch was interrupted or hit `--timeout` before the
+/// checks finished, or started
+pub const CANCELLED: i32 = 4;
+/// there is no pr to show, and none was created
+pub const NO_PR: i32 = 5;
+/// the backend couldn't be reached or turned a request down
+pub const BACKEND: i32 = 6;
+/// there are no usable credentials for the backend
+pub const AUTH: i32 = 7;
+/// the pr has no checks, still none had shown up when it was fetched again
+pub const NO_CHECKS: i32 = 8;
+
+/// the codes as `--help` lists them
+pub const HELP: &str = indoc! {"
+    EXIT CODES:
+        0    every check passed
+        1    something else went wrong, e.g. in git, a push or the editor
+        2    the arguments didn't make sense
+        3    at least one check failed
+        4    a check was cancelled or timed out, or the watch was interrupted or hit --timeout
+        5    there is no pr, and none was created
+        6    the backend couldn't be reached or turned a request down
+        7    there are no usable credentials for the backend
+        8    the pr has no checks, none showed up by the time it was fetched again
+"};
+
+/// the code for how all of a pr's checks turned out
+pub fn of_checks(outcome: CheckOutcome) -> i32 {
+    match outcome {
+        CheckOutcome::Passed => SUCCESS,
+        CheckOutcome::Failed => CHECKS_FAILED,
+        CheckOutcome::Cancelled | CheckOutcome::Pending => CANCELLED,
+    }
+}
+
+/// the code for an error that made it up to `main`
+pub fn of_error(error: &Error) -> i32 {
+    match error {
+        Error::Usage(_) => USAGE,
+        Error::NoPr(_) => NO_PR,
+        Error::Forge(_) => BACKEND,
+        Error::Auth(_) => AUTH,
+        Error::Git(_)
+        | Error::Repo(_)
+        | Error::Parse(_)
+        | Error::NotFound(_)
+        | Error::Push(..)
+        | Error::Template(_) => ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::forge::ForgeError;
+
+    #[test]
+    fn test_of_checks() {
+        assert_eq!(of_checks(CheckOutcome::Passed), SUCCESS);
+        assert_eq!(of_checks(CheckOutcome::Failed), CHECKS_FAILED);
+        assert_eq!(of_checks(CheckOutcome::Cancelled), CANCELLED);
+        assert_eq!(of_checks(CheckOutcome::Pending), CANCELLED);
+    }
+
+    #[test]
+    fn test_of_error() {
+        let forge = |e: ForgeError| of_error(&Error::from(e));
+        assert_eq!(forge(ForgeError::Auth("no token".to_string())), AUTH);
+        assert_eq!(forge(ForgeError::Request("timed out".to_string())), BACKEND);
+        assert_eq!(forge(ForgeError::NotFound("a url".to_string())), BACKEND);
+        assert_eq!(of_error(&Error::NoPr("no pr".to_string())), NO_PR);
+        assert_eq!(of_error(&Error::Usage("--url".to_string())), USAGE);
+        assert_eq!(of_error(&Error::Parse("a url".to_string())), ERROR);
+    }
+}
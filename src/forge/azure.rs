@@ -0,0 +1,780 @@
+use std::collections::{HashMap, HashSet};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use super::{found, http, split_head, token::azure_token, Forge, ForgeError, MergeMethod, NewPr};
+use crate::prinfo::{
+    CheckConclusionState, CheckStatusState, Commit, File, Label, Node, PrInfo, Repo, Review,
+    StatusCheck, StatusContextState, User,
+};
+
+const API_VERSION: &str = "7.0";
+
+/// talks to the azure devops (azure repos) rest api
+pub struct Azure {
+    api_url: String,
+    auth: String,
+    repo: String,
+}
+
+#[derive(Deserialize)]
+struct Values<T> {
+    value: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Identity {
+    id: String,
+    display_name: String,
+    unique_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Reviewer {
+    id: String,
+    display_name: String,
+    unique_name: Option<String>,
+    vote: i32,
+    #[serde(default)]
+    is_required: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitCommitRef {
+    commit_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Repository {
+    id: String,
+    name: String,
+    web_url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DefaultBranch {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrRef {
+    status: String,
+    source_ref_name: String,
+}
+
+#[derive(Deserialize)]
+struct QueryResults {
+    results: Vec<HashMap<String, Vec<PrRef>>>,
+}
+
+#[derive(Deserialize)]
+struct AzLabel {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequest {
+    pull_request_id: u32,
+    title: String,
+    description: Option<String>,
+    status: String,
+    #[serde(default)]
+    is_draft: bool,
+    created_by: Identity,
+    creation_date: String,
+    closed_date: Option<String>,
+    closed_by: Option<Identity>,
+    source_ref_name: String,
+    target_ref_name: String,
+    last_merge_source_commit: Option<GitCommitRef>,
+    last_merge_commit: Option<GitCommitRef>,
+    merge_status: Option<String>,
+    #[serde(default)]
+    reviewers: Vec<Reviewer>,
+    #[serde(default)]
+    labels: Vec<AzLabel>,
+    repository: Repository,
+    fork_source: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct StatusContext {
+    name: String,
+    genre: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrStatus {
+    state: String,
+    context: StatusContext,
+    target_url: Option<String>,
+    creation_date: String,
+}
+
+#[derive(Deserialize)]
+struct Definition {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct WebLink {
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct BuildLinks {
+    web: WebLink,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Build {
+    status: String,
+    result: Option<String>,
+    definition: Definition,
+    start_time: Option<String>,
+    finish_time: Option<String>,
+    #[serde(rename = "_links")]
+    links: BuildLinks,
+}
+
+#[derive(Deserialize)]
+struct Signature {
+    name: String,
+    email: String,
+    date: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzCommit {
+    commit_id: String,
+    author: Signature,
+    committer: Signature,
+    comment: String,
+}
+
+#[derive(Deserialize)]
+struct Iteration {
+    id: u32,
+}
+
+#[derive(Deserialize)]
+struct ChangeItem {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct Change {
+    item: ChangeItem,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Changes {
+    change_entries: Vec<Change>,
+}
+
+impl Azure {
+    /// `api_url` is the `_apis` root of a project, e.g. `https://dev.azure.com/org/project/_apis`
+    pub fn new(host: &str, api_url: &str, repo: &str) -> Result<Self, ForgeError> {
+        let token = azure_token().ok_or_else(|| {
+            ForgeError::Auth(format!(
+                "no token for {host}, set AZURE_DEVOPS_EXT_PAT to a personal access token"
+            ))
+        })?;
+        Ok(Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            auth: format!("Basic {}", STANDARD.encode(format!(":{token}"))),
+            repo: http::encode(repo),
+        })
+    }
+
+    /// the default `_apis` root and the repository name for a remote on `host` with `path`,
+    /// which comes in a few shapes:
+    ///
+    /// - `dev.azure.com`, `org/project/_git/repo`
+    /// - `ssh.dev.azure.com`, `v3/org/project/repo`
+    /// - `org.visualstudio.com`, `project/_git/repo`
+    pub fn api_url_and_repo(host: &str, path: &str) -> Option<(String, String)> {
+        let parts = path.split('/').collect::<Vec<&str>>();
+        let (org, project, repo) = match parts.as_slice() {
+            ["v3", org, project, repo] => (org.to_string(), project, repo),
+            [org, project, "_git", repo] => (org.to_string(), project, repo),
+            [project, "_git", repo] => (host.split('.').next()?.to_string(), project, repo),
+            _ => return None,
+        };
+        Some((
+            format!("https://dev.azure.com/{org}/{project}/_apis"),
+            repo.to_string(),
+        ))
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let url = format!("{}{path}", self.api_url);
+        http::request(method, &url)
+            .set("Authorization", &self.auth)
+            .query("api-version", API_VERSION)
+    }
+
+    fn repo_path(&self, path: &str) -> String {
+        format!("/git/repositories/{}{path}", self.repo)
+    }
+
+    /// `pr` with its statuses, builds, commits and changes
+    fn complete(&self, pr: PullRequest) -> Result<PrInfo, ForgeError> {
+        let path = self.repo_path(&format!("/pullRequests/{}", pr.pull_request_id));
+        let statuses: Values<PrStatus> = self.get(&format!("{path}/statuses"), &[])?;
+        let merge_ref = format!("refs/pull/{}/merge", pr.pull_request_id);
+        let builds: Values<Build> = self.get(
+            "/build/builds",
+            &[
+                ("branchName", &merge_ref),
+                ("repositoryId", &pr.repository.id),
+                ("repositoryType", "TfsGit"),
+                ("queryOrder", "queueTimeDescending"),
+            ],
+        )?;
+        let commits: Values<AzCommit> = self.get(&format!("{path}/commits"), &[])?;
+        let iterations: Values<Iteration> = self.get(&format!("{path}/iterations"), &[])?;
+        let changes = match iterations.value.last() {
+            Some(iteration) => {
+                self.get(&format!("{path}/iterations/{}/changes", iteration.id), &[])?
+            }
+            None => Changes {
+                change_entries: vec![],
+            },
+        };
+
+        Ok(to_pr_info(
+            pr,
+            statuses.value,
+            builds.value,
+            commits.value,
+            changes,
+        ))
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, ForgeError> {
+        let request = query
+            .iter()
+            .fold(self.request("GET", path), |request, (k, v)| {
+                request.query(k, v)
+            });
+        Ok(serde_json::from_value(http::send(request, None)?)?)
+    }
+}
+
+impl Forge for Azure {
+    fn name(&self) -> &'static str {
+        "azure"
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        // forks aren't told apart, the source branch is all there is to go on
+        let (_, branch) = split_head(head);
+        let source_ref = format!("refs/heads/{branch}");
+        let found: Values<PullRequest> = self.get(
+            &self.repo_path("/pullrequests"),
+            &[
+                ("searchCriteria.sourceRefName", &source_ref),
+                ("searchCriteria.status", "active"),
+            ],
+        )?;
+        match found.value.into_iter().next() {
+            Some(pr) => self.complete(pr).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        // the head commit is `lastMergeSourceCommit`, any commit of the pr is `commit`
+        for kind in ["lastMergeSourceCommit", "commit"] {
+            let found: QueryResults = serde_json::from_value(http::send(
+                self.request("POST", &self.repo_path("/pullrequestquery")),
+                Some(json!({ "queries": [{ "type": kind, "items": [sha] }] })),
+            )?)?;
+            let pr = found
+                .results
+                .into_iter()
+                .flat_map(|result| result.into_values().flatten())
+                .find(|pr| pr.status == "active");
+            if let Some(pr) = pr {
+                let branch = pr.source_ref_name.trim_start_matches("refs/heads/");
+                return self.fetch_pr(branch)
+            }
+        }
+        Ok(None)
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        let path = self.repo_path(&format!("/pullrequests/{number}"));
+        match found(self.get::<PullRequest>(&path, &[]))? {
+            Some(pr) => self.complete(pr).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        if let (Some(_), _) = split_head(&new_pr.head) {
+            return Err(ForgeError::Unsupported("prs from forks on azure devops"))
+        }
+        // the branch is pushed by now, so what azure can't take is left out rather than failing
+        // over, reviewers are identity ids on azure, which there is no way to look up from here
+        if !new_pr.reviewers.is_empty() {
+            warn!(
+                "azure devops can't request reviews by name, leaving out {}",
+                new_pr.reviewers.join(", ")
+            );
+        }
+        if !new_pr.assignees.is_empty() || new_pr.milestone.is_some() {
+            warn!("azure devops has no assignees or milestones, leaving them out");
+        }
+        let labels = new_pr
+            .labels
+            .iter()
+            .map(|name| json!({ "name": name }))
+            .collect::<Vec<_>>();
+        http::send(
+            self.request("POST", &self.repo_path("/pullrequests")),
+            Some(json!({
+                "sourceRefName": format!("refs/heads/{}", new_pr.head),
+                "targetRefName": format!("refs/heads/{}", new_pr.base),
+                "title": new_pr.title,
+                "description": new_pr.body,
+                "isDraft": new_pr.draft,
+                "labels": labels,
+            })),
+        )?;
+        self.fetch_pr(&new_pr.head)?.ok_or_else(|| {
+            ForgeError::Request(format!("no pr found for {} after creating it", new_pr.head))
+        })
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        let path = self.repo_path(&format!("/pullRequests/{}/threads", pr.number));
+        http::send(
+            self.request("POST", &path),
+            Some(json!({
+                "comments": [{ "content": body, "commentType": 1 }],
+                "status": 1,
+            })),
+        )
+        .map(|_| ())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        let strategy = match method {
+            MergeMethod::Merge => "noFastForward",
+            MergeMethod::Rebase => "rebase",
+            MergeMethod::Squash => "squash",
+        };
+        let path = self.repo_path(&format!("/pullrequests/{}", pr.number));
+        http::send(
+            self.request("PATCH", &path),
+            Some(json!({
+                "status": "completed",
+                "lastMergeSourceCommit": { "commitId": pr.headRefOid },
+                "completionOptions": { "mergeStrategy": strategy },
+            })),
+        )
+        .map(|_| ())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        let repository: DefaultBranch = self.get(&self.repo_path(""), &[])?;
+        let branch = repository.default_branch;
+        Ok(branch
+            .strip_prefix("refs/heads/")
+            .map(String::from)
+            .unwrap_or(branch))
+    }
+}
+
+fn to_user(identity: Identity) -> User {
+    User {
+        login: identity
+            .unique_name
+            .unwrap_or_else(|| identity.display_name.clone()),
+        email: None,
+        id: Some(identity.id),
+        name: Some(identity.display_name),
+    }
+}
+
+/// reviewer votes: 10 approved, 5 approved with suggestions, 0 no vote, -5 waiting for the
+/// author, -10 rejected
+fn vote_state(vote: i32) -> Option<&'static str> {
+    match vote {
+        vote if vote > 0 => Some("APPROVED"),
+        vote if vote < 0 => Some("CHANGES_REQUESTED"),
+        _ => None,
+    }
+}
+
+fn to_status_check(status: PrStatus) -> StatusCheck {
+    let state = match status.state.as_str() {
+        "succeeded" | "notApplicable" => StatusContextState::Success,
+        "failed" => StatusContextState::Failure,
+        "error" => StatusContextState::Error,
+        _ => StatusContextState::Pending,
+    };
+    let context = match status.context.genre {
+        Some(genre) if !genre.is_empty() => format!("{genre}/{}", status.context.name),
+        _ => status.context.name,
+    };
+    StatusCheck::StatusContext {
+        context,
+        startedAt: status.creation_date,
+        state,
+        targetUrl: status.target_url.unwrap_or_default(),
+    }
+}
+
+fn to_build_check(build: Build) -> StatusCheck {
+    let (status, conclusion) = match (build.status.as_str(), build.result.as_deref()) {
+        ("completed", Some("succeeded")) => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Success),
+        ),
+        ("completed", Some("partiallySucceeded")) => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Neutral),
+        ),
+        ("completed", Some("canceled")) => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Cancelled),
+        ),
+        ("completed", _) => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Failure),
+        ),
+        ("inProgress" | "cancelling", _) => (CheckStatusState::InProgress, None),
+        ("postponed", _) => (CheckStatusState::Waiting, None),
+        _ => (CheckStatusState::Queued, None),
+    };
+    StatusCheck::CheckRun {
+        completedAt: build.finish_time.unwrap_or_default(),
+        conclusion,
+        detailsUrl: build.links.web.href,
+        name: build.definition.name.clone(),
+        startedAt: build.start_time.unwrap_or_default(),
+        status,
+        workflowName: build.definition.name,
+    }
+}
+
+fn to_commit(commit: AzCommit) -> Commit {
+    let (headline, body) = match commit.comment.split_once('\n') {
+        Some((headline, body)) => (headline.to_string(), body.trim().to_string()),
+        None => (commit.comment, String::new()),
+    };
+    Commit {
+        authoredDate: commit.author.date,
+        authors: vec![User {
+            login: commit.author.email.clone(),
+            email: Some(commit.author.email),
+            id: None,
+            name: Some(commit.author.name),
+        }],
+        committedDate: commit.committer.date,
+        messageBody: body,
+        messageHeadline: headline,
+        oid: commit.commit_id,
+    }
+}
+
+fn to_pr_info(
+    pr: PullRequest,
+    statuses: Vec<PrStatus>,
+    builds: Vec<Build>,
+    commits: Vec<AzCommit>,
+    changes: Changes,
+) -> PrInfo {
+    // builds come newest first, only the latest run of each pipeline counts
+    let mut seen = HashSet::new();
+    let builds = builds
+        .into_iter()
+        .filter(|build| seen.insert(build.definition.name.clone()));
+    let status_check_rollup = builds
+        .map(to_build_check)
+        .chain(statuses.into_iter().map(to_status_check))
+        .collect();
+
+    let votes = pr
+        .reviewers
+        .iter()
+        .map(|reviewer| (reviewer.is_required, vote_state(reviewer.vote)))
+        .collect::<Vec<(bool, Option<&str>)>>();
+    let review_decision = if votes
+        .iter()
+        .any(|(_, vote)| *vote == Some("CHANGES_REQUESTED"))
+    {
+        "CHANGES_REQUESTED"
+    } else if votes.iter().any(|(_, vote)| vote.is_some())
+        && votes
+            .iter()
+            .all(|(required, vote)| !required || vote.is_some())
+    {
+        "APPROVED"
+    } else {
+        "REVIEW_REQUIRED"
+    };
+    let (voted, pending): (Vec<Reviewer>, Vec<Reviewer>) = pr
+        .reviewers
+        .into_iter()
+        .partition(|reviewer| vote_state(reviewer.vote).is_some());
+    let reviews: Vec<Review> = voted
+        .into_iter()
+        .map(|reviewer| Review {
+            id: reviewer.id.clone(),
+            state: vote_state(reviewer.vote).unwrap_or_default().to_string(),
+            author: to_user(Identity {
+                id: reviewer.id,
+                display_name: reviewer.display_name,
+                unique_name: reviewer.unique_name,
+            }),
+            ..Default::default()
+        })
+        .collect();
+
+    // azure lists commits newest first, gh oldest first
+    let mut commits: Vec<Commit> = commits.into_iter().map(to_commit).collect();
+    commits.reverse();
+    let files: Vec<File> = changes
+        .change_entries
+        .into_iter()
+        .map(|change| File {
+            path: change.item.path.trim_start_matches('/').to_string(),
+            additions: 0,
+            deletions: 0,
+        })
+        .collect();
+    let head_ref_oid = pr
+        .last_merge_source_commit
+        .map(|commit| commit.commit_id)
+        .unwrap_or_default();
+
+    PrInfo {
+        author: to_user(pr.created_by),
+        baseRefName: pr
+            .target_ref_name
+            .trim_start_matches("refs/heads/")
+            .to_string(),
+        body: pr.description.unwrap_or_default(),
+        changedFiles: files.len() as u32,
+        closed: pr.status != "active",
+        closedAt: pr.closed_date,
+        commits,
+        createdAt: pr.creation_date,
+        files,
+        headRefName: pr
+            .source_ref_name
+            .trim_start_matches("refs/heads/")
+            .to_string(),
+        headRefOid: head_ref_oid,
+        headRepository: Repo {
+            id: pr.repository.id,
+            name: pr.repository.name,
+        },
+        id: pr.pull_request_id.to_string(),
+        isCrossRepository: pr.fork_source.is_some(),
+        isDraft: pr.is_draft,
+        labels: pr
+            .labels
+            .into_iter()
+            .map(|label| Label {
+                id: label.id,
+                name: label.name,
+                ..Default::default()
+            })
+            .collect(),
+        latestReviews: reviews.clone(),
+        mergeCommit: pr.last_merge_commit.map(|commit| Node {
+            oid: commit.commit_id,
+        }),
+        mergeable: match pr.merge_status.as_deref() {
+            Some("conflicts") => "CONFLICTING",
+            Some("succeeded") => "MERGEABLE",
+            _ => "UNKNOWN",
+        }
+        .to_string(),
+        mergedBy: pr
+            .closed_by
+            .filter(|_| pr.status == "completed")
+            .map(to_user),
+        number: pr.pull_request_id,
+        reviewDecision: review_decision.to_string(),
+        reviewRequests: pending
+            .into_iter()
+            .map(|reviewer| reviewer.unique_name.unwrap_or(reviewer.display_name))
+            .collect(),
+        reviews,
+        state: match pr.status.as_str() {
+            "active" => "OPEN",
+            "completed" => "MERGED",
+            _ => "CLOSED",
+        }
+        .to_string(),
+        statusCheckRollup: status_check_rollup,
+        title: pr.title,
+        url: format!(
+            "{}/pullrequest/{}",
+            pr.repository.web_url, pr.pull_request_id
+        ),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::from_str;
+
+    use super::*;
+    use crate::forge::http::stand_in::StandIn;
+
+    fn fixture_pr_info() -> PrInfo {
+        let prs: Values<PullRequest> = from_str(include_str!("mock_data/azure_prs.json")).unwrap();
+        let statuses: Values<PrStatus> =
+            from_str(include_str!("mock_data/azure_statuses.json")).unwrap();
+        let builds: Values<Build> = from_str(include_str!("mock_data/azure_builds.json")).unwrap();
+        let commits: Values<AzCommit> =
+            from_str(include_str!("mock_data/azure_commits.json")).unwrap();
+        let changes: Changes = from_str(include_str!("mock_data/azure_changes.json")).unwrap();
+        let pr = prs.value.into_iter().next().unwrap();
+        to_pr_info(pr, statuses.value, builds.value, commits.value, changes)
+    }
+
+    #[test]
+    fn test_pr_fields() {
+        let pr_info = fixture_pr_info();
+        assert_eq!(pr_info.number, 42);
+        assert_eq!(pr_info.title, "Add retry to the uploader");
+        assert_eq!(pr_info.state, "OPEN");
+        assert_eq!(pr_info.headRefName, "feature/retry");
+        assert_eq!(pr_info.baseRefName, "main");
+        assert_eq!(
+            pr_info.url,
+            "https://dev.azure.com/contoso/Fabrikam/_git/uploader/pullrequest/42"
+        );
+        assert_eq!(pr_info.sha(), "b1f7a9c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8");
+        assert_eq!(
+            pr_info
+                .files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["src/upload.rs", "README.md"]
+        );
+    }
+
+    #[test]
+    fn test_builds_and_statuses_become_checks() {
+        let checks = fixture_pr_info().statusCheckRollup;
+        let summary = checks
+            .iter()
+            .map(|check| (check.name(), check.short_status_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("uploader-ci".to_string(), "Fail".to_string()),
+                ("uploader-lint".to_string(), " .. ".to_string()),
+                ("codecoverage/coverage".to_string(), "Pass".to_string()),
+            ]
+        );
+        assert!(!fixture_pr_info().is_complete());
+    }
+
+    #[test]
+    fn test_votes_become_reviews() {
+        let pr_info = fixture_pr_info();
+        let reviews = pr_info
+            .reviews
+            .iter()
+            .map(|review| (review.author.login.as_str(), review.state.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reviews,
+            vec![
+                ("alice@contoso.com", "APPROVED"),
+                ("bob@contoso.com", "CHANGES_REQUESTED"),
+            ]
+        );
+        assert_eq!(pr_info.reviewRequests, vec!["[Fabrikam]\\Uploader Team"]);
+        assert_eq!(pr_info.reviewDecision, "CHANGES_REQUESTED");
+    }
+
+    #[test]
+    fn test_create_pr_leaves_out_what_azure_lacks() {
+        let server = StandIn::serve(&[
+            (201, "{}"),
+            (200, include_str!("mock_data/azure_prs.json")),
+            (200, include_str!("mock_data/azure_statuses.json")),
+            (200, include_str!("mock_data/azure_builds.json")),
+            (200, include_str!("mock_data/azure_commits.json")),
+            (200, r#"{"value": [{"id": 1}]}"#),
+            (200, include_str!("mock_data/azure_changes.json")),
+        ]);
+        let azure = Azure {
+            api_url: server.url.clone(),
+            auth: "Basic dDBrZW4=".to_string(),
+            repo: "uploader".to_string(),
+        };
+        let new_pr = NewPr {
+            title: "Add retry to the uploader".to_string(),
+            body: String::new(),
+            head: "feature/retry".to_string(),
+            base: "main".to_string(),
+            draft: false,
+            reviewers: vec!["alice".to_string()],
+            assignees: vec!["bob".to_string()],
+            labels: vec!["uploads".to_string()],
+            milestone: Some("v2".to_string()),
+        };
+        let pr_info = azure.create_pr(&new_pr).unwrap();
+        assert_eq!(pr_info.number, 42);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 7);
+        assert!(requests[0].starts_with("POST "), "{}", requests[0]);
+        assert!(requests[0].contains(r#""labels":[{"name":"uploads"}]"#), "{}", requests[0]);
+        assert!(!requests[0].contains("alice"), "{}", requests[0]);
+    }
+
+    #[test]
+    fn test_api_url_and_repo() {
+        let expected = Some((
+            "https://dev.azure.com/contoso/Fabrikam/_apis".to_string(),
+            "uploader".to_string(),
+        ));
+        assert_eq!(
+            Azure::api_url_and_repo("dev.azure.com", "contoso/Fabrikam/_git/uploader"),
+            expected
+        );
+        assert_eq!(
+            Azure::api_url_and_repo("ssh.dev.azure.com", "v3/contoso/Fabrikam/uploader"),
+            expected
+        );
+        assert_eq!(
+            Azure::api_url_and_repo("contoso.visualstudio.com", "Fabrikam/_git/uploader"),
+            expected
+        );
+    }
+}
@@ -0,0 +1,644 @@
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{found, http, split_head, token::bitbucket_token, Forge, ForgeError, MergeMethod, NewPr};
+use crate::prinfo::{
+    CheckConclusionState, CheckStatusState, Commit, File, PrInfo, Repo, Review, StatusCheck, User,
+};
+
+/// talks to the bitbucket server (data center) rest api, not bitbucket.org
+pub struct BitbucketServer {
+    host_url: String,
+    token: String,
+    project: String,
+    repo: String,
+}
+
+/// how many items a page of a list holds, bitbucket server's default cap on `limit`
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    values: Vec<T>,
+    #[serde(default = "last_page")]
+    is_last_page: bool,
+    next_page_start: Option<u64>,
+}
+
+/// a page that doesn't say is taken to be the only one
+fn last_page() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BbUser {
+    id: u64,
+    name: String,
+    display_name: Option<String>,
+    email_address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Participant {
+    user: BbUser,
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct BbProject {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct BbRepo {
+    id: u64,
+    slug: String,
+    project: BbProject,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BbRef {
+    display_id: String,
+    latest_commit: String,
+    repository: BbRepo,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DefaultBranch {
+    display_id: String,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+#[derive(Deserialize)]
+struct Links {
+    #[serde(rename = "self")]
+    self_links: Vec<Link>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequest {
+    id: u32,
+    version: u32,
+    title: String,
+    description: Option<String>,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    created_date: i64,
+    updated_date: i64,
+    closed_date: Option<i64>,
+    from_ref: BbRef,
+    to_ref: BbRef,
+    author: Participant,
+    #[serde(default)]
+    reviewers: Vec<Participant>,
+    links: Links,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BbCommit {
+    id: String,
+    message: String,
+    author: BbUser,
+    author_timestamp: i64,
+    committer_timestamp: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Path {
+    to_string: String,
+}
+
+#[derive(Deserialize)]
+struct Change {
+    path: Path,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildStatus {
+    state: String,
+    key: String,
+    name: Option<String>,
+    url: String,
+    date_added: i64,
+}
+
+impl BitbucketServer {
+    /// `host_url` is the root of the server, e.g. `https://bitbucket.example.com`, the rest and
+    /// build status apis hang off of it
+    pub fn new(host: &str, host_url: &str, project: &str, repo: &str) -> Result<Self, ForgeError> {
+        let token = bitbucket_token().ok_or_else(|| {
+            ForgeError::Auth(format!(
+                "no token for {host}, set BITBUCKET_TOKEN to an http access token"
+            ))
+        })?;
+        Ok(Self {
+            host_url: host_url.trim_end_matches('/').to_string(),
+            token,
+            project: http::encode(project),
+            repo: http::encode(repo),
+        })
+    }
+
+    /// the `(project, repo)` of a remote path, http remotes live under `scm/`
+    pub fn project_and_repo(path: &str) -> Option<(String, String)> {
+        let path = path.strip_prefix("scm/").unwrap_or(path);
+        let (project, repo) = path.split_once('/')?;
+        Some((project.to_string(), repo.to_string()))
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        http::request(method, &format!("{}{path}", self.host_url))
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    fn repo_path(&self, path: &str) -> String {
+        format!(
+            "/rest/api/1.0/projects/{}/repos/{}{path}",
+            self.project, self.repo
+        )
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, ForgeError> {
+        let request = query
+            .iter()
+            .fold(self.request("GET", path), |request, (k, v)| {
+                request.query(k, v)
+            });
+        Ok(serde_json::from_value(http::send(request, None)?)?)
+    }
+
+    /// every item of a list, a page at a time until the server says it was the last
+    fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, ForgeError> {
+        let mut all = vec![];
+        let mut start = 0;
+        loop {
+            let (limit, from) = (PAGE_SIZE.to_string(), start.to_string());
+            let mut query = query.to_vec();
+            query.extend([("limit", limit.as_str()), ("start", from.as_str())]);
+            let page: Page<T> = self.get(path, &query)?;
+            all.extend(page.values);
+            match page.next_page_start {
+                Some(next) if !page.is_last_page => start = next,
+                _ => break,
+            }
+        }
+        Ok(all)
+    }
+
+    /// `pr` with its commits, changes and build statuses
+    fn complete(&self, pr: PullRequest) -> Result<PrInfo, ForgeError> {
+        let path = self.repo_path(&format!("/pull-requests/{}", pr.id));
+        let commits: Vec<BbCommit> = self.get_all(&format!("{path}/commits"), &[])?;
+        let changes: Vec<Change> = self.get_all(&format!("{path}/changes"), &[])?;
+        let builds: Vec<BuildStatus> = self.get_all(
+            &format!(
+                "/rest/build-status/1.0/commits/{}",
+                pr.from_ref.latest_commit
+            ),
+            &[],
+        )?;
+
+        Ok(to_pr_info(pr, commits, changes, builds))
+    }
+
+    fn pull_request(&self, number: u32) -> Result<PullRequest, ForgeError> {
+        self.get(&self.repo_path(&format!("/pull-requests/{number}")), &[])
+    }
+}
+
+impl Forge for BitbucketServer {
+    fn name(&self) -> &'static str {
+        "bitbucket"
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        // forks aren't told apart, the source branch is all there is to go on
+        let (_, branch) = split_head(head);
+        let at = format!("refs/heads/{branch}");
+        let found: Page<PullRequest> = self.get(
+            &self.repo_path("/pull-requests"),
+            &[("at", &at), ("direction", "OUTGOING"), ("state", "OPEN")],
+        )?;
+        match found.values.into_iter().next() {
+            Some(pr) => self.complete(pr).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        let found: Page<PullRequest> = self.get(
+            &self.repo_path(&format!("/commits/{sha}/pull-requests")),
+            &[],
+        )?;
+        let open = found
+            .values
+            .into_iter()
+            .filter(|pr| pr.state == "OPEN")
+            .collect::<Vec<PullRequest>>();
+        // prefer the pr `sha` is the head of over one it is merely part of
+        let head = open
+            .iter()
+            .position(|pr| pr.from_ref.latest_commit == sha)
+            .unwrap_or(0);
+        open.into_iter()
+            .nth(head)
+            .map(|pr| self.complete(pr))
+            .transpose()
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        match found(self.pull_request(number))? {
+            Some(pr) => self.complete(pr).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        if let (Some(_), _) = split_head(&new_pr.head) {
+            return Err(ForgeError::Unsupported(
+                "prs from forks on bitbucket server",
+            ))
+        }
+        // the branch is pushed by now, so what bitbucket can't take is left out rather than
+        // failing over
+        let (users, teams) = new_pr.reviewers();
+        if !teams.is_empty() {
+            let teams = teams
+                .iter()
+                .map(|(org, team)| format!("{org}/{team}"))
+                .collect::<Vec<String>>();
+            warn!(
+                "bitbucket server has no team reviewers, leaving out {}",
+                teams.join(", ")
+            );
+        }
+        if !new_pr.assignees.is_empty() || !new_pr.labels.is_empty() || new_pr.milestone.is_some() {
+            warn!("bitbucket server has no assignees, labels or milestones, leaving them out");
+        }
+        let reviewers = users
+            .iter()
+            .map(|name| json!({ "user": { "name": name } }))
+            .collect::<Vec<_>>();
+        http::send(
+            self.request("POST", &self.repo_path("/pull-requests")),
+            Some(json!({
+                "title": new_pr.title,
+                "description": new_pr.body,
+                "draft": new_pr.draft,
+                "fromRef": { "id": format!("refs/heads/{}", new_pr.head) },
+                "toRef": { "id": format!("refs/heads/{}", new_pr.base) },
+                "reviewers": reviewers,
+            })),
+        )?;
+        self.fetch_pr(&new_pr.head)?.ok_or_else(|| {
+            ForgeError::Request(format!("no pr found for {} after creating it", new_pr.head))
+        })
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        let path = self.repo_path(&format!("/pull-requests/{}/comments", pr.number));
+        http::send(self.request("POST", &path), Some(json!({ "text": body }))).map(|_| ())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        let strategy = match method {
+            MergeMethod::Merge => "no-ff",
+            MergeMethod::Rebase => "rebase-no-ff",
+            MergeMethod::Squash => "squash",
+        };
+        // merging needs the version we are merging, so the server can reject stale requests
+        let version = self.pull_request(pr.number)?.version.to_string();
+        let path = self.repo_path(&format!("/pull-requests/{}/merge", pr.number));
+        http::send(
+            self.request("POST", &path).query("version", &version),
+            Some(json!({ "strategyId": strategy })),
+        )
+        .map(|_| ())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        let branch: DefaultBranch = self.get(&self.repo_path("/default-branch"), &[])?;
+        Ok(branch.display_id)
+    }
+}
+
+/// bitbucket hands out milliseconds since the epoch, the models want rfc3339 like github
+fn to_timestamp(millis: i64) -> String {
+    OffsetDateTime::from_unix_timestamp_nanos(millis as i128 * 1_000_000)
+        .ok()
+        .and_then(|time| time.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+fn to_user(user: BbUser) -> User {
+    User {
+        login: user.name,
+        email: user.email_address,
+        id: Some(user.id.to_string()),
+        name: user.display_name,
+    }
+}
+
+fn to_check(build: BuildStatus) -> StatusCheck {
+    let (status, conclusion) = match build.state.as_str() {
+        "SUCCESSFUL" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Success),
+        ),
+        "FAILED" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Failure),
+        ),
+        "CANCELLED" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Cancelled),
+        ),
+        _ => (CheckStatusState::InProgress, None),
+    };
+    StatusCheck::CheckRun {
+        completedAt: match status.is_complete() {
+            true => to_timestamp(build.date_added),
+            false => String::new(),
+        },
+        conclusion,
+        detailsUrl: build.url,
+        name: build.name.unwrap_or_else(|| build.key.clone()),
+        startedAt: to_timestamp(build.date_added),
+        status,
+        workflowName: build.key,
+    }
+}
+
+fn to_commit(commit: BbCommit) -> Commit {
+    let (headline, body) = match commit.message.split_once('\n') {
+        Some((headline, body)) => (headline.to_string(), body.trim().to_string()),
+        None => (commit.message, String::new()),
+    };
+    Commit {
+        authoredDate: to_timestamp(commit.author_timestamp),
+        authors: vec![to_user(commit.author)],
+        committedDate: to_timestamp(commit.committer_timestamp),
+        messageBody: body,
+        messageHeadline: headline,
+        oid: commit.id,
+    }
+}
+
+fn to_pr_info(
+    pr: PullRequest,
+    commits: Vec<BbCommit>,
+    changes: Vec<Change>,
+    builds: Vec<BuildStatus>,
+) -> PrInfo {
+    let review_decision = if pr.reviewers.iter().any(|r| r.status == "NEEDS_WORK") {
+        "CHANGES_REQUESTED"
+    } else if pr.reviewers.iter().any(|r| r.status == "APPROVED") {
+        "APPROVED"
+    } else {
+        "REVIEW_REQUIRED"
+    };
+    let (voted, pending): (Vec<Participant>, Vec<Participant>) = pr
+        .reviewers
+        .into_iter()
+        .partition(|reviewer| reviewer.status != "UNAPPROVED");
+    let reviews: Vec<Review> = voted
+        .into_iter()
+        .map(|reviewer| Review {
+            id: reviewer.user.id.to_string(),
+            state: match reviewer.status.as_str() {
+                "NEEDS_WORK" => "CHANGES_REQUESTED".to_string(),
+                status => status.to_string(),
+            },
+            author: to_user(reviewer.user),
+            ..Default::default()
+        })
+        .collect();
+
+    // bitbucket lists commits newest first, gh oldest first
+    let mut commits: Vec<Commit> = commits.into_iter().map(to_commit).collect();
+    commits.reverse();
+    let files: Vec<File> = changes
+        .into_iter()
+        .map(|change| File {
+            path: change.path.to_string,
+            additions: 0,
+            deletions: 0,
+        })
+        .collect();
+
+    PrInfo {
+        author: to_user(pr.author.user),
+        baseRefName: pr.to_ref.display_id,
+        body: pr.description.unwrap_or_default(),
+        changedFiles: files.len() as u32,
+        closed: pr.state != "OPEN",
+        closedAt: pr.closed_date.map(to_timestamp),
+        commits,
+        createdAt: to_timestamp(pr.created_date),
+        files,
+        headRefName: pr.from_ref.display_id,
+        headRefOid: pr.from_ref.latest_commit,
+        headRepository: Repo {
+            id: pr.from_ref.repository.id.to_string(),
+            name: pr.from_ref.repository.slug,
+        },
+        headRepositoryOwner: User {
+            login: pr.from_ref.repository.project.key,
+            ..Default::default()
+        },
+        id: pr.id.to_string(),
+        isCrossRepository: pr.from_ref.repository.id != pr.to_ref.repository.id,
+        isDraft: pr.draft,
+        latestReviews: reviews.clone(),
+        number: pr.id,
+        reviewDecision: review_decision.to_string(),
+        reviewRequests: pending
+            .into_iter()
+            .map(|reviewer| reviewer.user.name)
+            .collect(),
+        reviews,
+        state: match pr.state.as_str() {
+            "OPEN" => "OPEN",
+            "MERGED" => "MERGED",
+            _ => "CLOSED",
+        }
+        .to_string(),
+        statusCheckRollup: builds.into_iter().map(to_check).collect(),
+        title: pr.title,
+        updatedAt: to_timestamp(pr.updated_date),
+        url: pr
+            .links
+            .self_links
+            .into_iter()
+            .next()
+            .map(|link| link.href)
+            .unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::{from_str, Value};
+
+    use super::*;
+    use crate::forge::http::stand_in::StandIn;
+
+    fn fixture_pr_info() -> PrInfo {
+        let prs: Page<PullRequest> =
+            from_str(include_str!("mock_data/bitbucket_prs.json")).unwrap();
+        let commits: Page<BbCommit> =
+            from_str(include_str!("mock_data/bitbucket_commits.json")).unwrap();
+        let changes: Page<Change> =
+            from_str(include_str!("mock_data/bitbucket_changes.json")).unwrap();
+        let builds: Page<BuildStatus> =
+            from_str(include_str!("mock_data/bitbucket_builds.json")).unwrap();
+        let pr = prs.values.into_iter().next().unwrap();
+        to_pr_info(pr, commits.values, changes.values, builds.values)
+    }
+
+    #[test]
+    fn test_pr_fields() {
+        let pr_info = fixture_pr_info();
+        assert_eq!(pr_info.number, 101);
+        assert_eq!(pr_info.title, "Cache dependency resolution");
+        assert_eq!(pr_info.state, "OPEN");
+        assert_eq!(pr_info.headRefName, "feature/cache-deps");
+        assert_eq!(pr_info.baseRefName, "master");
+        assert_eq!(pr_info.createdAt, "2023-01-10T09:00:00Z");
+        assert_eq!(
+            pr_info.url,
+            "https://bitbucket.example.com/projects/BLD/repos/builder/pull-requests/101"
+        );
+        assert_eq!(pr_info.sha(), "6f1e2d3c4b5a69788796a5b4c3d2e1f0a9b8c7d6");
+        assert_eq!(pr_info.commits[0].messageHeadline, "Hash the lockfile");
+        assert_eq!(pr_info.files.len(), 2);
+    }
+
+    #[test]
+    fn test_build_statuses_become_checks() {
+        let pr_info = fixture_pr_info();
+        let summary = pr_info
+            .statusCheckRollup
+            .iter()
+            .map(|check| (check.name(), check.short_status_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("Unit tests".to_string(), " OK ".to_string()),
+                ("integration".to_string(), " .. ".to_string()),
+            ]
+        );
+        assert!(!pr_info.is_complete());
+    }
+
+    #[test]
+    fn test_reviewer_status_becomes_reviews() {
+        let pr_info = fixture_pr_info();
+        let reviews = pr_info
+            .reviews
+            .iter()
+            .map(|review| (review.author.login.as_str(), review.state.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(reviews, vec![("carol", "APPROVED")]);
+        assert_eq!(pr_info.reviewRequests, vec!["dave"]);
+        assert_eq!(pr_info.reviewDecision, "APPROVED");
+    }
+
+    #[test]
+    fn test_create_pr_leaves_out_what_bitbucket_lacks() {
+        let server = StandIn::serve(&[
+            (201, "{}"),
+            (200, include_str!("mock_data/bitbucket_prs.json")),
+            (200, include_str!("mock_data/bitbucket_commits.json")),
+            (200, include_str!("mock_data/bitbucket_changes.json")),
+            (200, include_str!("mock_data/bitbucket_builds.json")),
+        ]);
+        let bitbucket = BitbucketServer {
+            host_url: server.url.clone(),
+            token: "t0ken".to_string(),
+            project: "OPS".to_string(),
+            repo: "builder".to_string(),
+        };
+        let new_pr = NewPr {
+            title: "Cache dependency resolution".to_string(),
+            body: String::new(),
+            head: "feature/cache-deps".to_string(),
+            base: "main".to_string(),
+            draft: false,
+            reviewers: vec!["alice".to_string(), "ops/builders".to_string()],
+            assignees: vec!["bob".to_string()],
+            labels: vec!["builds".to_string()],
+            milestone: Some("v2".to_string()),
+        };
+        let pr_info = bitbucket.create_pr(&new_pr).unwrap();
+        assert_eq!(pr_info.number, 101);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 5);
+        assert!(requests[0].starts_with("POST "), "{}", requests[0]);
+        assert!(
+            requests[0].contains(r#""reviewers":[{"user":{"name":"alice"}}]"#),
+            "{}",
+            requests[0]
+        );
+    }
+
+    #[test]
+    fn test_project_and_repo() {
+        let expected = Some(("BLD".to_string(), "builder".to_string()));
+        assert_eq!(
+            BitbucketServer::project_and_repo("scm/BLD/builder"),
+            expected
+        );
+        assert_eq!(BitbucketServer::project_and_repo("BLD/builder"), expected);
+    }
+
+    #[test]
+    fn test_get_all_pages() {
+        let page: Value = from_str(include_str!("mock_data/bitbucket_commits.json")).unwrap();
+        let mut first_page = page.clone();
+        first_page["isLastPage"] = false.into();
+        first_page["nextPageStart"] = 2.into();
+        let (first_page, last_page) = (first_page.to_string(), page.to_string());
+        let server = StandIn::serve(&[(200, &first_page), (200, &last_page)]);
+        let bitbucket = BitbucketServer {
+            host_url: server.url.clone(),
+            token: "t0ken".to_string(),
+            project: "OPS".to_string(),
+            repo: "builder".to_string(),
+        };
+        let commits: Vec<BbCommit> = bitbucket
+            .get_all(&bitbucket.repo_path("/pull-requests/101/commits"), &[])
+            .unwrap();
+        assert_eq!(commits.len(), 4);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("start=0"), "{}", requests[0]);
+        assert!(requests[1].contains("start=2"), "{}", requests[1]);
+    }
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use super::{Forge, ForgeError, MergeMethod, NewPr};
+use crate::{
+    plan::{self, Action},
+    prinfo::{Label, PrInfo, StatusCheck},
+};
+
+/// stands in for another backend under `--dry-run`, fetches go through to it so the output is
+/// real, anything that would change the forge is printed as part of the plan instead
+pub struct DryRun {
+    inner: Arc<dyn Forge>,
+}
+
+impl DryRun {
+    pub fn new(inner: Arc<dyn Forge>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Forge for DryRun {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        self.inner.fetch_pr(head)
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        self.inner.fetch_pr_by_sha(sha)
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        self.inner.fetch_pr_by_number(number)
+    }
+
+    /// the pr as it would have been opened, it has no number, checks or url yet
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        plan::print(&Action::CreatePr {
+            forge: self.inner.name(),
+            pr: new_pr.clone(),
+        });
+        Ok(PrInfo {
+            title: new_pr.title.clone(),
+            body: new_pr.body.clone(),
+            headRefName: new_pr.head.clone(),
+            baseRefName: new_pr.base.clone(),
+            isDraft: new_pr.draft,
+            state: "OPEN".to_string(),
+            reviewRequests: new_pr.reviewers.clone(),
+            assignees: new_pr.assignees.clone(),
+            labels: new_pr
+                .labels
+                .iter()
+                .map(|name| Label {
+                    name: name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            milestone: new_pr.milestone.clone(),
+            ..Default::default()
+        })
+    }
+
+    fn list_checks(&self, pr: &PrInfo) -> Result<Vec<StatusCheck>, ForgeError> {
+        self.inner.list_checks(pr)
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        plan::print(&Action::Comment {
+            forge: self.inner.name(),
+            number: pr.number,
+            body: body.to_string(),
+        });
+        Ok(())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        plan::print(&Action::Merge {
+            forge: self.inner.name(),
+            number: pr.number,
+            method,
+        });
+        Ok(())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        self.inner.default_branch()
+    }
+
+    fn viewer(&self) -> Result<Option<String>, ForgeError> {
+        self.inner.viewer()
+    }
+}
@@ -0,0 +1,89 @@
+use std::{fs, path::Path};
+
+use log::info;
+
+use super::{gh::parse_pr_list, Forge, ForgeError, MergeMethod, NewPr};
+use crate::prinfo::{Label, PrInfo};
+
+/// serves a canned pr from a `gh pr list --json` dump, handy for working on the ui offline
+pub struct Fixture {
+    json: String,
+}
+
+impl Fixture {
+    /// one of the built in fixtures, or a path to a json file
+    pub fn load(name_or_path: &str) -> Result<Self, ForgeError> {
+        let json = match name_or_path {
+            "fix-main" => include_str!("../prinfo/mock_data/d1.json").to_string(),
+            "simple" => include_str!("../prinfo/mock_data/d2.json").to_string(),
+            "pending" => include_str!("../prinfo/mock_data/d3.json").to_string(),
+            "building" => include_str!("../prinfo/mock_data/d4.json").to_string(),
+            "done" => include_str!("../prinfo/mock_data/d2.json").to_string(),
+            path => fs::read_to_string(Path::new(path)).map_err(|e| {
+                ForgeError::Command(format!("no fixture named or at {path:?}: {e}"))
+            })?,
+        };
+        Ok(Self { json })
+    }
+}
+
+impl Forge for Fixture {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    fn fetch_pr(&self, _head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        Ok(parse_pr_list(&self.json)?.into_iter().next())
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        Ok(self
+            .fetch_pr("")?
+            .filter(|pr| pr.headRefOid == sha || pr.commits.iter().any(|commit| commit.oid == sha)))
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        Ok(self.fetch_pr("")?.filter(|pr| pr.number == number))
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        let pr_info = self.fetch_pr(&new_pr.head)?.ok_or(ForgeError::Unsupported(
+            "creating a pr from an empty fixture",
+        ))?;
+        Ok(PrInfo {
+            title: new_pr.title.clone(),
+            body: new_pr.body.clone(),
+            isDraft: new_pr.draft,
+            reviewRequests: new_pr.reviewers.clone(),
+            assignees: new_pr.assignees.clone(),
+            labels: new_pr
+                .labels
+                .iter()
+                .map(|name| Label {
+                    name: name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            milestone: new_pr.milestone.clone(),
+            ..pr_info
+        })
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        info!("fixture: comment on #{}: {body}", pr.number);
+        Ok(())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        info!("fixture: merge #{} with {method:?}", pr.number);
+        Ok(())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        self.fetch_pr("")?
+            .map(|pr| pr.baseRefName)
+            .ok_or(ForgeError::Unsupported(
+                "a default branch for an empty fixture",
+            ))
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use log::warn;
+use serde::Deserialize;
+use serde_json::from_str;
+
+use super::{found, is_from, split_head, Forge, ForgeError, MergeMethod, NewPr};
+use crate::{
+    prinfo::{PrInfo, StatusCheck},
+    shell::{Cmd, ShellError},
+};
+
+/// fields that older github enterprise servers don't have, or that github has since removed
+const OPTIONAL_FIELDS: &[&str] = &[
+    "latestReviews",
+    "mergeStateStatus",
+    "projectCards",
+    "reactionGroups",
+    "reviewDecision",
+];
+
+/// the env vars gh takes a token from
+const TOKEN_VARS: &[&str] = &[
+    "GH_TOKEN",
+    "GITHUB_TOKEN",
+    "GH_ENTERPRISE_TOKEN",
+    "GITHUB_ENTERPRISE_TOKEN",
+];
+
+/// talks to github through the `gh` cli
+pub struct GhCli {
+    /// `host/owner/repo`, passed to every command so gh doesn't fall back to github.com
+    repo: String,
+    /// set once the server has turned down the optional fields
+    without_optional: AtomicBool,
+}
+
+impl GhCli {
+    pub fn new(host: &str, path: &str) -> Self {
+        Self {
+            repo: format!("{host}/{path}"),
+            without_optional: AtomicBool::new(false),
+        }
+    }
+
+    fn pr_list(&self, head: &str, fields: &[&str]) -> Result<Option<PrInfo>, ForgeError> {
+        // gh only filters on the branch name, forks are told apart here
+        let (owner, branch) = split_head(head);
+        let prs = parse_pr_list(&gh(&[
+            "pr",
+            "list",
+            "--repo",
+            &self.repo,
+            "--json",
+            &fields.join(","),
+            "--head",
+            branch,
+        ])?)?;
+        Ok(prs.into_iter().find(|pr| is_from(pr, owner)))
+    }
+
+    /// run `query` with every field, falling back to leaving out the optional ones for good if
+    /// gh or the server turns one of them down
+    fn with_fields<T>(
+        &self,
+        query: impl Fn(&[&str]) -> Result<T, ForgeError>,
+    ) -> Result<T, ForgeError> {
+        let required = PrInfo::FIELD_NAMES_AS_ARRAY
+            .iter()
+            .filter(|field| !OPTIONAL_FIELDS.contains(field))
+            .copied()
+            .collect::<Vec<&str>>();
+        if self.without_optional.load(Ordering::Relaxed) {
+            return query(&required)
+        }
+        match query(PrInfo::FIELD_NAMES_AS_ARRAY) {
+            Err(e) if is_unknown_field(&e) => {
+                let found = query(&required).map_err(|_| e)?;
+                warn!(
+                    "{} turned down some pr fields, leaving out {}",
+                    self.repo,
+                    OPTIONAL_FIELDS.join(", ")
+                );
+                self.without_optional.store(true, Ordering::Relaxed);
+                Ok(found)
+            }
+            found => found,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BranchRef {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RepoView {
+    default_branch_ref: BranchRef,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checks {
+    status_check_rollup: Vec<StatusCheck>,
+}
+
+/// parse the output of `gh pr list --json ...`, which is always a list
+pub(crate) fn parse_pr_list(stdout: &str) -> Result<Vec<PrInfo>, ForgeError> {
+    if stdout.trim().is_empty() {
+        return Ok(vec![])
+    }
+    Ok(from_str::<Vec<PrInfo>>(stdout)?)
+}
+
+/// whether gh failed over a field, an older gh doesn't know it, an older github enterprise server
+/// doesn't have it
+fn is_unknown_field(e: &ForgeError) -> bool {
+    match e {
+        ForgeError::Command(message) => {
+            message.contains("Unknown JSON field") || message.contains("doesn't exist on type")
+        }
+        _ => false,
+    }
+}
+
+/// run gh with `args` and return what it printed
+fn gh(args: &[&str]) -> Result<String, ForgeError> {
+    let cmd = Cmd::new("gh")
+        .args(args.iter().copied())
+        .env("GH_PROMPT_DISABLED", "1");
+    // gh reads its token from these, keep them out of anything it echoes back
+    let cmd = TOKEN_VARS
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .fold(cmd, Cmd::secret);
+    match cmd.run() {
+        Ok(output) => Ok(output.stdout),
+        Err(ShellError::Failed(cmd, _, stderr)) if is_logged_out(&stderr) => {
+            Err(ForgeError::Auth(format!(
+                "{cmd}: {}, run `gh auth login`",
+                stderr.trim()
+            )))
+        }
+        Err(ShellError::Failed(cmd, _, stderr))
+            if stderr.contains("Could not resolve to a PullRequest") =>
+        {
+            Err(ForgeError::NotFound(cmd))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// whether gh failed for want of a login, or a token that works
+fn is_logged_out(stderr: &str) -> bool {
+    ["gh auth login", "HTTP 401", "Bad credentials"]
+        .iter()
+        .any(|message| stderr.contains(message))
+}
+
+impl Forge for GhCli {
+    fn name(&self) -> &'static str {
+        "gh"
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        self.with_fields(|fields| self.pr_list(head, fields))
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        // searching for a sha finds the prs that contain it, only the head is needed from them
+        let found = parse_pr_list(&gh(&[
+            "pr",
+            "list",
+            "--repo",
+            &self.repo,
+            "--state",
+            "open",
+            "--search",
+            sha,
+            "--json",
+            "headRefName,headRefOid,headRepositoryOwner,isCrossRepository",
+        ])?)?;
+        let found = found
+            .iter()
+            .find(|pr| pr.headRefOid == sha)
+            .or(found.first());
+        match found {
+            Some(pr) => self.fetch_pr(&pr.head()),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        self.with_fields(|fields| {
+            let stdout = gh(&[
+                "pr",
+                "view",
+                &number.to_string(),
+                "--repo",
+                &self.repo,
+                "--json",
+                &fields.join(","),
+            ]);
+            match found(stdout)? {
+                Some(stdout) => Ok(Some(from_str(&stdout)?)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        let NewPr {
+            title,
+            body,
+            head,
+            base,
+            draft,
+            reviewers,
+            assignees,
+            labels,
+            milestone,
+        } = new_pr;
+        let mut args = vec![
+            "pr", "create", "--repo", &self.repo, "--title", title, "--body", body, "--head", head,
+            "--base", base,
+        ];
+        if *draft {
+            args.push("--draft");
+        }
+        // gh sets these up as part of creating the pr, and takes `org/team` reviewers as they are
+        let (reviewers, assignees, labels) =
+            (reviewers.join(","), assignees.join(","), labels.join(","));
+        for (flag, value) in [
+            ("--reviewer", &reviewers),
+            ("--assignee", &assignees),
+            ("--label", &labels),
+        ] {
+            if !value.is_empty() {
+                args.extend([flag, value]);
+            }
+        }
+        if let Some(milestone) = milestone {
+            args.extend(["--milestone", milestone]);
+        }
+        gh(&args)?;
+        self.fetch_pr(head)?
+            .ok_or_else(|| ForgeError::Command(format!("no pr found for {head} after creating it")))
+    }
+
+    fn list_checks(&self, pr: &PrInfo) -> Result<Vec<StatusCheck>, ForgeError> {
+        let stdout = gh(&[
+            "pr",
+            "view",
+            &pr.number.to_string(),
+            "--repo",
+            &self.repo,
+            "--json",
+            "statusCheckRollup",
+        ])?;
+        Ok(from_str::<Checks>(&stdout)?.status_check_rollup)
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        gh(&[
+            "pr",
+            "comment",
+            &pr.number.to_string(),
+            "--repo",
+            &self.repo,
+            "--body",
+            body,
+        ])
+        .map(|_| ())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        let method_arg = match method {
+            MergeMethod::Merge => "--merge",
+            MergeMethod::Rebase => "--rebase",
+            MergeMethod::Squash => "--squash",
+        };
+        gh(&[
+            "pr",
+            "merge",
+            &pr.number.to_string(),
+            "--repo",
+            &self.repo,
+            method_arg,
+        ])
+        .map(|_| ())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        let stdout = gh(&["repo", "view", &self.repo, "--json", "defaultBranchRef"])?;
+        Ok(from_str::<RepoView>(&stdout)?.default_branch_ref.name)
+    }
+
+    fn viewer(&self) -> Result<Option<String>, ForgeError> {
+        let host = self.repo.split('/').next().unwrap_or_default();
+        let login = gh(&["api", "--hostname", host, "user", "--jq", ".login"])?;
+        Ok(Some(login.trim().to_string()).filter(|login| !login.is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_pr_list_with_assignments() {
+        let prs = parse_pr_list(
+            r#"[{
+                "number": 7,
+                "assignees": [{"id": "U1", "login": "alice", "name": "Alice"}],
+                "reviewRequests": [
+                    {"__typename": "User", "login": "bob"},
+                    {"__typename": "Team", "name": "Uploaders", "slug": "uploaders"}
+                ],
+                "milestone": {"number": 1, "title": "v2", "description": "", "dueOn": null}
+            }]"#,
+        )
+        .unwrap();
+        assert_eq!(prs[0].assignees, vec!["alice"]);
+        assert_eq!(prs[0].reviewRequests, vec!["bob", "uploaders"]);
+        assert_eq!(prs[0].milestone.as_deref(), Some("v2"));
+    }
+
+    #[test]
+    fn test_with_fields_falls_back_on_unknown_fields_only() {
+        let gh = GhCli::new("github.com", "octo/uploader");
+        let offline = gh.with_fields(|_| -> Result<(), ForgeError> {
+            Err(ForgeError::Command("error connecting to api.github.com".to_string()))
+        });
+        assert!(offline.is_err());
+        assert!(!gh.without_optional.load(Ordering::Relaxed));
+
+        let fields = gh.with_fields(|fields| match fields.contains(&"latestReviews") {
+            true => Err(ForgeError::Command(
+                "Unknown JSON field: \"latestReviews\"".to_string(),
+            )),
+            false => Ok(fields.len()),
+        });
+        assert_eq!(
+            fields.unwrap(),
+            PrInfo::FIELD_NAMES_AS_ARRAY.len() - OPTIONAL_FIELDS.len()
+        );
+        assert!(gh.without_optional.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_is_logged_out() {
+        assert!(is_logged_out(
+            "To get started with GitHub CLI, please run:  gh auth login"
+        ));
+        assert!(is_logged_out("HTTP 401: Bad credentials (https://api.github.com/graphql)"));
+        assert!(!is_logged_out(
+            "GraphQL: Could not resolve to a PullRequest with the number of 7."
+        ));
+    }
+}
@@ -0,0 +1,744 @@
+use std::collections::HashMap;
+
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use super::{found, http, split_head, token::gitea_token, Forge, ForgeError, MergeMethod, NewPr};
+use crate::prinfo::{
+    Commit, File, Label, Node, PrInfo, Repo, Review, StatusCheck, StatusContextState, User,
+};
+
+/// how many items a page of a list holds, gitea's default cap on `limit`
+const PAGE_SIZE: usize = 50;
+
+/// talks to the gitea (and forgejo, which shares the api) v1 rest api
+pub struct Gitea {
+    api_url: String,
+    token: String,
+    owner: String,
+    repo: String,
+}
+
+#[derive(Deserialize)]
+struct GtUser {
+    id: u64,
+    login: String,
+    full_name: Option<String>,
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GtLabel {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    color: String,
+}
+
+#[derive(Deserialize)]
+struct Milestone {
+    id: Option<u64>,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct GtRepo {
+    id: u64,
+    name: String,
+    owner: GtUser,
+}
+
+#[derive(Deserialize)]
+struct RepoSettings {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct Branch {
+    #[serde(rename = "ref")]
+    ref_name: String,
+    sha: String,
+    repo: Option<GtRepo>,
+}
+
+#[derive(Deserialize)]
+struct Pull {
+    id: u64,
+    number: u32,
+    title: String,
+    body: Option<String>,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    html_url: String,
+    user: GtUser,
+    #[serde(default)]
+    assignees: Option<Vec<GtUser>>,
+    #[serde(default)]
+    requested_reviewers: Option<Vec<GtUser>>,
+    #[serde(default)]
+    labels: Vec<GtLabel>,
+    milestone: Option<Milestone>,
+    head: Branch,
+    base: Branch,
+    #[serde(default)]
+    merged: bool,
+    merged_at: Option<String>,
+    merged_by: Option<GtUser>,
+    merge_commit_sha: Option<String>,
+    #[serde(default)]
+    mergeable: bool,
+    created_at: String,
+    updated_at: String,
+    closed_at: Option<String>,
+    #[serde(default)]
+    additions: u32,
+    #[serde(default)]
+    deletions: u32,
+    #[serde(default)]
+    changed_files: u32,
+}
+
+#[derive(Deserialize)]
+struct GitSignature {
+    name: String,
+    email: String,
+    date: String,
+}
+
+#[derive(Deserialize)]
+struct GitCommit {
+    message: String,
+    author: GitSignature,
+    committer: GitSignature,
+}
+
+#[derive(Deserialize)]
+struct GtCommit {
+    sha: String,
+    commit: GitCommit,
+    author: Option<GtUser>,
+}
+
+#[derive(Deserialize)]
+struct GtFile {
+    filename: String,
+    additions: usize,
+    deletions: usize,
+}
+
+#[derive(Deserialize)]
+struct GtReview {
+    id: u64,
+    user: Option<GtUser>,
+    state: String,
+    #[serde(default)]
+    body: String,
+    submitted_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CommitStatus {
+    context: String,
+    status: String,
+    target_url: Option<String>,
+    created_at: String,
+}
+
+#[derive(Deserialize)]
+struct CombinedStatus {
+    #[serde(default)]
+    statuses: Option<Vec<CommitStatus>>,
+}
+
+impl Pull {
+    /// whether the head is on `owner`'s fork, or on the repo itself without an owner
+    fn is_from(&self, owner: Option<&str>) -> bool {
+        let head_repo = self.head.repo.as_ref();
+        match owner {
+            Some(owner) => head_repo.map(|repo| repo.owner.login.as_str()) == Some(owner),
+            None => head_repo.map(|repo| repo.id) == self.base.repo.as_ref().map(|repo| repo.id),
+        }
+    }
+}
+
+impl Gitea {
+    /// `api_url` is the v1 api root, e.g. `https://codeberg.org/api/v1`
+    pub fn new(host: &str, api_url: &str, owner: &str, repo: &str) -> Result<Self, ForgeError> {
+        let token = gitea_token(host).ok_or_else(|| {
+            ForgeError::Auth(format!(
+                "no token for {host}, set GITEA_TOKEN or log in with `tea login add`"
+            ))
+        })?;
+        Ok(Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+            owner: http::encode(owner),
+            repo: http::encode(repo),
+        })
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let url = format!("{}/repos/{}/{}{path}", self.api_url, self.owner, self.repo);
+        http::request(method, &url).set("Authorization", &format!("token {}", self.token))
+    }
+
+    /// ask `new_pr`'s reviewers to review pr `number`, gitea's teams are the repo owner's so
+    /// those of another org are left out
+    fn request_reviewers(&self, number: u32, new_pr: &NewPr) -> Result<(), ForgeError> {
+        let (reviewers, teams) = new_pr.reviewers();
+        let mut team_reviewers = vec![];
+        for (org, team) in teams {
+            match org.eq_ignore_ascii_case(&self.owner) {
+                true => team_reviewers.push(team),
+                false => warn!(
+                    "gitea can only ask teams of {} for reviews, leaving out {org}/{team}",
+                    self.owner
+                ),
+            }
+        }
+        if reviewers.is_empty() && team_reviewers.is_empty() {
+            return Ok(())
+        }
+        http::send(
+            self.request("POST", &format!("/pulls/{number}/requested_reviewers")),
+            Some(json!({ "reviewers": reviewers, "team_reviewers": team_reviewers })),
+        )
+        .map(|_| ())
+    }
+
+    /// `pull` with its commits, files, reviews and statuses
+    fn complete(&self, pull: Pull) -> Result<PrInfo, ForgeError> {
+        let path = format!("/pulls/{}", pull.number);
+        let commits: Vec<GtCommit> = self.get_all(&format!("{path}/commits"), &[])?;
+        let files: Vec<GtFile> = self.get_all(&format!("{path}/files"), &[])?;
+        let reviews: Vec<GtReview> = self.get_all(&format!("{path}/reviews"), &[])?;
+        let status: CombinedStatus =
+            self.get(&format!("/commits/{}/status", pull.head.sha), &[])?;
+
+        Ok(to_pr_info(pull, commits, files, reviews, status))
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, ForgeError> {
+        let request = query
+            .iter()
+            .fold(self.request("GET", path), |request, (k, v)| {
+                request.query(k, v)
+            });
+        Ok(serde_json::from_value(http::send(request, None)?)?)
+    }
+
+    /// page `page` of a list, counting from 1
+    fn get_page<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        page: usize,
+    ) -> Result<Vec<T>, ForgeError> {
+        let (limit, page) = (PAGE_SIZE.to_string(), page.to_string());
+        let mut query = query.to_vec();
+        query.extend([("limit", limit.as_str()), ("page", page.as_str())]);
+        self.get(path, &query)
+    }
+
+    /// every item of a list, a page at a time until one comes back short
+    fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, ForgeError> {
+        let mut all = vec![];
+        for page in 1.. {
+            let items: Vec<T> = self.get_page(path, query, page)?;
+            let last = items.len() < PAGE_SIZE;
+            all.extend(items);
+            if last {
+                break
+            }
+        }
+        Ok(all)
+    }
+
+    /// the first open pr that `matches`, the pages after it aren't fetched
+    fn find_open_pull(&self, matches: impl Fn(&Pull) -> bool) -> Result<Option<Pull>, ForgeError> {
+        for page in 1.. {
+            let pulls: Vec<Pull> = self.get_page("/pulls", &[("state", "open")], page)?;
+            let last = pulls.len() < PAGE_SIZE;
+            if let Some(pull) = pulls.into_iter().find(&matches) {
+                return Ok(Some(pull))
+            }
+            if last {
+                break
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Forge for Gitea {
+    fn name(&self) -> &'static str {
+        "gitea"
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        let (owner, branch) = split_head(head);
+        let Some(pull) =
+            self.find_open_pull(|pull| pull.head.ref_name == branch && pull.is_from(owner))?
+        else {
+            return Ok(None)
+        };
+        self.complete(pull).map(Some)
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        let head = self.find_open_pull(|pull| pull.head.sha == sha)?;
+        // gitea 1.18+ can also find the pr a commit came in with
+        let head = match head {
+            Some(pull) => Some(pull),
+            None => self
+                .get::<Pull>(&format!("/commits/{sha}/pull"), &[])
+                .ok()
+                .filter(|pull| pull.state == "open"),
+        };
+        head.map(|pull| self.complete(pull)).transpose()
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        match found(self.get(&format!("/pulls/{number}"), &[]))? {
+            Some(pull) => self.complete(pull).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        // labels and the milestone are looked up before the pr is opened, so a typo doesn't
+        // leave one behind that is only half set up
+        let label_ids = match new_pr.labels.is_empty() {
+            true => vec![],
+            false => {
+                let labels: Vec<GtLabel> = self.get_all("/labels", &[])?;
+                new_pr
+                    .labels
+                    .iter()
+                    .map(|name| {
+                        labels
+                            .iter()
+                            .find(|label| &label.name == name)
+                            .map(|label| label.id)
+                            .ok_or_else(|| ForgeError::Request(format!("no label {name}")))
+                    })
+                    .collect::<Result<Vec<u64>, ForgeError>>()?
+            }
+        };
+        let milestone_id = match &new_pr.milestone {
+            Some(title) => {
+                let milestones: Vec<Milestone> = self.get("/milestones", &[("name", title)])?;
+                let milestone = milestones
+                    .iter()
+                    .find(|milestone| &milestone.title == title)
+                    .and_then(|milestone| milestone.id)
+                    .ok_or_else(|| ForgeError::Request(format!("no milestone {title}")))?;
+                Some(milestone)
+            }
+            None => None,
+        };
+        // gitea marks work in progress prs by their title
+        let title = match new_pr.draft {
+            true => format!("WIP: {}", new_pr.title),
+            false => new_pr.title.clone(),
+        };
+        let pull: Pull = serde_json::from_value(http::send(
+            self.request("POST", "/pulls"),
+            Some(json!({
+                "head": new_pr.head,
+                "base": new_pr.base,
+                "title": title,
+                "body": new_pr.body,
+                "assignees": new_pr.assignees,
+                "labels": label_ids,
+                "milestone": milestone_id,
+            })),
+        )?)?;
+        // review requests can only be made once there is a pr to make them on, the pr is there
+        // either way so failing to make them isn't worth failing over
+        if let Err(e) = self.request_reviewers(pull.number, new_pr) {
+            warn!("opened #{} but could not request its reviewers: {e}", pull.number);
+        }
+        // the pr as it is now, with its reviewers, or as it was opened if that can't be had
+        let pull = self
+            .get(&format!("/pulls/{}", pull.number), &[])
+            .unwrap_or(pull);
+        self.complete(pull)
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        let path = format!("/issues/{}/comments", pr.number);
+        http::send(self.request("POST", &path), Some(json!({ "body": body }))).map(|_| ())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        let method = match method {
+            MergeMethod::Merge => "merge",
+            MergeMethod::Rebase => "rebase",
+            MergeMethod::Squash => "squash",
+        };
+        let path = format!("/pulls/{}/merge", pr.number);
+        http::send(self.request("POST", &path), Some(json!({ "Do": method }))).map(|_| ())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        let repo: RepoSettings = self.get("", &[])?;
+        Ok(repo.default_branch)
+    }
+
+    fn viewer(&self) -> Result<Option<String>, ForgeError> {
+        let request = http::request("GET", &format!("{}/user", self.api_url))
+            .set("Authorization", &format!("token {}", self.token));
+        let user: GtUser = serde_json::from_value(http::send(request, None)?)?;
+        Ok(Some(user.login))
+    }
+}
+
+fn to_user(user: GtUser) -> User {
+    User {
+        login: user.login,
+        email: user.email.filter(|email| !email.is_empty()),
+        id: Some(user.id.to_string()),
+        name: user.full_name.filter(|name| !name.is_empty()),
+    }
+}
+
+/// commit statuses map straight onto github's status contexts
+fn to_check(status: CommitStatus) -> StatusCheck {
+    let state = match status.status.as_str() {
+        "success" => StatusContextState::Success,
+        "error" => StatusContextState::Error,
+        "failure" | "warning" => StatusContextState::Failure,
+        _ => StatusContextState::Pending,
+    };
+    StatusCheck::StatusContext {
+        context: status.context,
+        startedAt: status.created_at,
+        state,
+        targetUrl: status.target_url.unwrap_or_default(),
+    }
+}
+
+fn to_commit(commit: GtCommit) -> Commit {
+    let (headline, body) = commit
+        .commit
+        .message
+        .split_once('\n')
+        .map(|(headline, body)| (headline.to_string(), body.trim().to_string()))
+        .unwrap_or_else(|| (commit.commit.message.trim().to_string(), String::new()));
+    let author = commit.commit.author;
+    Commit {
+        authoredDate: author.date,
+        authors: vec![User {
+            login: commit
+                .author
+                .map(|user| user.login)
+                .unwrap_or_else(|| author.name.clone()),
+            email: Some(author.email),
+            id: None,
+            name: Some(author.name),
+        }],
+        committedDate: commit.commit.committer.date,
+        messageBody: body,
+        messageHeadline: headline,
+        oid: commit.sha,
+    }
+}
+
+fn to_review(review: GtReview) -> Option<Review> {
+    Some(Review {
+        id: review.id.to_string(),
+        author: to_user(review.user?),
+        body: review.body,
+        submittedAt: review.submitted_at.unwrap_or_default(),
+        state: match review.state.as_str() {
+            "REQUEST_CHANGES" => "CHANGES_REQUESTED".to_string(),
+            "COMMENT" => "COMMENTED".to_string(),
+            state => state.to_string(),
+        },
+        ..Default::default()
+    })
+}
+
+fn to_pr_info(
+    pull: Pull,
+    commits: Vec<GtCommit>,
+    files: Vec<GtFile>,
+    reviews: Vec<GtReview>,
+    status: CombinedStatus,
+) -> PrInfo {
+    let reviews: Vec<Review> = reviews.into_iter().filter_map(to_review).collect();
+    // the decision goes by each reviewer's latest review
+    let latest_states = reviews
+        .iter()
+        .map(|review| (review.author.login.as_str(), review.state.as_str()))
+        .collect::<HashMap<&str, &str>>();
+    let review_decision = if latest_states
+        .values()
+        .any(|state| *state == "CHANGES_REQUESTED")
+    {
+        "CHANGES_REQUESTED"
+    } else if latest_states.values().any(|state| *state == "APPROVED") {
+        "APPROVED"
+    } else {
+        "REVIEW_REQUIRED"
+    };
+    let head_repo = pull.head.repo;
+    let is_cross_repository = match (&head_repo, &pull.base.repo) {
+        (Some(head), Some(base)) => head.id != base.id,
+        _ => false,
+    };
+
+    PrInfo {
+        additions: pull.additions,
+        assignees: pull
+            .assignees
+            .unwrap_or_default()
+            .into_iter()
+            .map(|user| user.login)
+            .collect(),
+        author: to_user(pull.user),
+        baseRefName: pull.base.ref_name,
+        body: pull.body.unwrap_or_default(),
+        changedFiles: pull.changed_files,
+        closed: pull.state == "closed",
+        closedAt: pull.closed_at,
+        commits: commits.into_iter().map(to_commit).collect(),
+        createdAt: pull.created_at,
+        deletions: pull.deletions,
+        files: files
+            .into_iter()
+            .map(|file| File {
+                path: file.filename,
+                additions: file.additions,
+                deletions: file.deletions,
+            })
+            .collect(),
+        headRefName: pull.head.ref_name,
+        headRefOid: pull.head.sha,
+        headRepository: head_repo
+            .as_ref()
+            .map(|repo| Repo {
+                id: repo.id.to_string(),
+                name: repo.name.clone(),
+            })
+            .unwrap_or_default(),
+        headRepositoryOwner: head_repo
+            .map(|repo| to_user(repo.owner))
+            .unwrap_or_default(),
+        id: pull.id.to_string(),
+        isCrossRepository: is_cross_repository,
+        isDraft: pull.draft,
+        labels: pull
+            .labels
+            .into_iter()
+            .map(|label| Label {
+                id: label.id.to_string(),
+                name: label.name,
+                description: label.description,
+                color: label.color,
+            })
+            .collect(),
+        latestReviews: reviews.clone(),
+        mergeCommit: pull.merge_commit_sha.map(|oid| Node { oid }),
+        mergeable: match pull.mergeable {
+            true => "MERGEABLE",
+            false => "CONFLICTING",
+        }
+        .to_string(),
+        mergedAt: pull.merged_at,
+        mergedBy: pull.merged_by.map(to_user),
+        milestone: pull.milestone.map(|milestone| milestone.title),
+        number: pull.number,
+        reviewDecision: review_decision.to_string(),
+        reviewRequests: pull
+            .requested_reviewers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|user| user.login)
+            .collect(),
+        reviews,
+        state: match (pull.merged, pull.state.as_str()) {
+            (true, _) => "MERGED",
+            (false, "open") => "OPEN",
+            _ => "CLOSED",
+        }
+        .to_string(),
+        statusCheckRollup: status
+            .statuses
+            .unwrap_or_default()
+            .into_iter()
+            .map(to_check)
+            .collect(),
+        title: pull.title,
+        updatedAt: pull.updated_at,
+        url: pull.html_url,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::{from_str, Value};
+
+    use super::*;
+    use crate::forge::http::stand_in::StandIn;
+
+    fn fixture_pr_info() -> PrInfo {
+        let pull: Pull = from_str(include_str!("mock_data/gitea_pull.json")).unwrap();
+        let commits: Vec<GtCommit> =
+            from_str(include_str!("mock_data/gitea_commits.json")).unwrap();
+        let files: Vec<GtFile> = from_str(include_str!("mock_data/gitea_files.json")).unwrap();
+        let reviews: Vec<GtReview> =
+            from_str(include_str!("mock_data/gitea_reviews.json")).unwrap();
+        let status: CombinedStatus =
+            from_str(include_str!("mock_data/gitea_status.json")).unwrap();
+        to_pr_info(pull, commits, files, reviews, status)
+    }
+
+    #[test]
+    fn test_pr_fields() {
+        let pr_info = fixture_pr_info();
+        assert_eq!(pr_info.number, 3);
+        assert_eq!(pr_info.state, "OPEN");
+        assert_eq!(pr_info.headRefName, "feature/retry-uploads");
+        assert_eq!(pr_info.baseRefName, "main");
+        assert!(!pr_info.isCrossRepository);
+        assert_eq!(pr_info.headRepositoryOwner.login, "octo");
+        assert_eq!(pr_info.assignees, vec!["alice"]);
+        assert_eq!(pr_info.reviewRequests, vec!["bob"]);
+        assert_eq!(pr_info.milestone.as_deref(), Some("v2"));
+        assert_eq!(pr_info.commits[0].messageHeadline, "Retry uploads");
+        assert_eq!(pr_info.commits[0].messageBody, "With a backoff.");
+        assert_eq!(pr_info.sha(), "b7e23ec29af22b0b4e41da31e868d57226121c84");
+        assert_eq!(pr_info.files.len(), 2);
+    }
+
+    #[test]
+    fn test_statuses_become_checks() {
+        let pr_info = fixture_pr_info();
+        let summary = pr_info
+            .statusCheckRollup
+            .iter()
+            .map(|check| (check.name(), check.short_status_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("ci/test".to_string(), "Pass".to_string()),
+                ("ci/lint".to_string(), "Fail".to_string()),
+                ("ci/deploy".to_string(), " .. ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_latest_review_decides() {
+        let pr_info = fixture_pr_info();
+        assert_eq!(pr_info.reviews.len(), 2);
+        assert_eq!(pr_info.reviews[0].state, "CHANGES_REQUESTED");
+        assert_eq!(pr_info.reviewDecision, "APPROVED");
+    }
+
+    #[test]
+    fn test_find_open_pull_pages() {
+        let pull: Value = from_str(include_str!("mock_data/gitea_pull.json")).unwrap();
+        let other = |number: usize| {
+            let mut other = pull.clone();
+            other["number"] = number.into();
+            other["head"]["ref"] = format!("feature/{number}").into();
+            other
+        };
+        let full_page = Value::Array((100..100 + PAGE_SIZE).map(other).collect()).to_string();
+        let last_page = Value::Array(vec![other(1), pull]).to_string();
+        let server = StandIn::serve(&[(200, &full_page), (200, &last_page)]);
+        let gitea = Gitea {
+            api_url: server.url.clone(),
+            token: "t0ken".to_string(),
+            owner: "octo".to_string(),
+            repo: "uploader".to_string(),
+        };
+        let found = gitea
+            .find_open_pull(|pull| pull.head.ref_name == "feature/retry-uploads")
+            .unwrap();
+        assert_eq!(found.map(|pull| pull.number), Some(3));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("page=2"), "{}", requests[1]);
+    }
+
+    #[test]
+    fn test_fetch_missing_pr() {
+        let server = StandIn::serve(&[(404, r#"{"message": "The target couldn't be found."}"#)]);
+        let gitea = Gitea {
+            api_url: server.url.clone(),
+            token: "t0ken".to_string(),
+            owner: "octo".to_string(),
+            repo: "uploader".to_string(),
+        };
+        assert!(gitea.fetch_pr_by_number(404).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_create_pr_outlives_review_requests() {
+        let fixture = |name| match name {
+            "pull" => include_str!("mock_data/gitea_pull.json"),
+            "commits" => include_str!("mock_data/gitea_commits.json"),
+            "files" => include_str!("mock_data/gitea_files.json"),
+            "reviews" => include_str!("mock_data/gitea_reviews.json"),
+            _ => include_str!("mock_data/gitea_status.json"),
+        };
+        let server = StandIn::serve(&[
+            (201, fixture("pull")),
+            (422, r#"{"message": "reviewer is not a collaborator"}"#),
+            (200, fixture("pull")),
+            (200, fixture("commits")),
+            (200, fixture("files")),
+            (200, fixture("reviews")),
+            (200, fixture("status")),
+        ]);
+        let gitea = Gitea {
+            api_url: server.url.clone(),
+            token: "t0ken".to_string(),
+            owner: "octo".to_string(),
+            repo: "uploader".to_string(),
+        };
+        let new_pr = NewPr {
+            title: "Retry uploads".to_string(),
+            body: String::new(),
+            head: "feature/retry-uploads".to_string(),
+            base: "main".to_string(),
+            draft: false,
+            reviewers: vec![
+                "bob".to_string(),
+                "octo/uploaders".to_string(),
+                "elsewhere/uploaders".to_string(),
+            ],
+            assignees: vec![],
+            labels: vec![],
+            milestone: None,
+        };
+        let pr_info = gitea.create_pr(&new_pr).unwrap();
+        assert_eq!(pr_info.number, 3);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 7);
+        assert!(
+            requests[1].ends_with(r#"{"reviewers":["bob"],"team_reviewers":["uploaders"]}"#),
+            "{}",
+            requests[1]
+        );
+    }
+}
@@ -0,0 +1,999 @@
+use std::{collections::HashSet, fmt, sync::Mutex};
+
+use log::warn;
+use serde_json::{from_value, json, Map, Value};
+
+use super::{
+    http, is_from, split_head, token::github_token, Forge, ForgeError, MergeMethod, NewPr,
+};
+use crate::prinfo::PrInfo;
+
+pub const GITHUB_GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// the selections of the review fragment
+const REVIEW_FIELDS: &[&str] = &[
+    "id",
+    "author { login }",
+    "authorAssociation",
+    "body",
+    "submittedAt",
+    "includesCreatedEdit",
+    "state",
+];
+
+/// the top level selections of the pr fragment, any field an older github enterprise server
+/// doesn't know about is left out of them, see `Selection`
+const PR_FIELDS: &[&str] = &[
+    "id",
+    "number",
+    "title",
+    "body",
+    "url",
+    "state",
+    "isDraft",
+    "closed",
+    "closedAt",
+    "createdAt",
+    "updatedAt",
+    "mergedAt",
+    "additions",
+    "deletions",
+    "changedFiles",
+    "baseRefName",
+    "headRefName",
+    "headRefOid",
+    "isCrossRepository",
+    "maintainerCanModify",
+    "mergeStateStatus",
+    "mergeable",
+    "reviewDecision",
+    "author { login }",
+    "headRepository { id name }",
+    "headRepositoryOwner { id login }",
+    "mergeCommit { oid }",
+    "potentialMergeCommit { oid }",
+    "mergedBy { login }",
+    "milestone { title }",
+    "assignees(first: 100) { nodes { login } }",
+    "labels(first: 100) { nodes { id name description color } }",
+    "files(first: 100) { nodes { path additions deletions } }",
+    "reviewRequests(first: 100) {
+      nodes { requestedReviewer { ... on User { login } ... on Team { slug } } }
+    }",
+    "latestReviews(first: 100) { nodes { ...review } }",
+    "reviews(first: 100) { nodes { ...review } }",
+    "comments(first: 100) {
+      nodes {
+        id author { login } authorAssociation body createdAt includesCreatedEdit
+        isMinimized minimizedReason url viewerDidAuthor
+      }
+    }",
+    "commits(last: 100) {
+      nodes {
+        commit {
+          oid authoredDate committedDate messageHeadline messageBody
+          authors(first: 10) { nodes { name email user { id login } } }
+        }
+      }
+    }",
+    "lastCommit: commits(last: 1) {
+      nodes {
+        commit {
+          statusCheckRollup {
+            contexts(first: 100) {
+              nodes {
+                __typename
+                ... on CheckRun {
+                  name status conclusion startedAt completedAt detailsUrl
+                  checkSuite { workflowRun { workflow { name } } }
+                }
+                ... on StatusContext { context state targetUrl createdAt }
+              }
+            }
+          }
+        }
+      }
+    }",
+];
+
+/// the graphql endpoint of `host`, github.com has its own api host while enterprise servers
+/// serve it under `/api`
+pub fn graphql_url(host: &str) -> String {
+    match host {
+        "github.com" => GITHUB_GRAPHQL_URL.to_string(),
+        host => format!("https://{host}/api/graphql"),
+    }
+}
+
+/// whether `field` shows up as a whole word in a selection
+fn selects(selection: &str, field: &str) -> bool {
+    selection
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == field)
+}
+
+/// a field, fragment spread or inline fragment of a graphql selection set, parsed just far enough
+/// that a field can be taken out of it without taking what is around it too
+#[derive(Debug)]
+struct Selection {
+    /// the field's name, not its alias, or the fragment's name or type condition
+    name: String,
+    /// the source up to the sub-selections, e.g. `lastCommit: commits(last: 1)`
+    head: String,
+    /// `None` for a leaf
+    selections: Option<Vec<Selection>>,
+}
+
+impl Selection {
+    /// parse a selection set, without its outer braces
+    fn parse(source: &str) -> Vec<Selection> {
+        let mut tokens = vec![];
+        let mut chars = source.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let mut end = start + c.len_utf8();
+            match c {
+                c if c.is_whitespace() || c == ',' => continue,
+                '.' => {
+                    // `...`
+                    chars.next();
+                    chars.next();
+                    end = start + 3;
+                }
+                '"' => {
+                    let mut escaped = false;
+                    for (i, c) in chars.by_ref() {
+                        end = i + c.len_utf8();
+                        match c {
+                            '"' if !escaped => break,
+                            '\\' => escaped = !escaped,
+                            _ => escaped = false,
+                        }
+                    }
+                }
+                c if c.is_alphanumeric() || c == '_' || c == '$' => {
+                    while let Some(&(i, c)) = chars.peek() {
+                        if !(c.is_alphanumeric() || c == '_') {
+                            break
+                        }
+                        end = i + c.len_utf8();
+                        chars.next();
+                    }
+                }
+                _ => {}
+            }
+            tokens.push((start, end));
+        }
+        Self::parse_tokens(source, &tokens, &mut 0)
+    }
+
+    fn parse_tokens(source: &str, tokens: &[(usize, usize)], at: &mut usize) -> Vec<Selection> {
+        let token = |i: usize| tokens.get(i).map(|&(start, end)| &source[start..end]);
+        let mut selections = vec![];
+        while let Some(first) = token(*at) {
+            if first == "}" {
+                break
+            }
+            let start = tokens[*at].0;
+            *at += 1;
+            let name = match first {
+                "..." if token(*at) == Some("on") => {
+                    *at += 2;
+                    token(*at - 1)
+                }
+                "..." => {
+                    *at += 1;
+                    token(*at - 1)
+                }
+                _ if token(*at) == Some(":") => {
+                    *at += 2;
+                    token(*at - 1)
+                }
+                name => Some(name),
+            }
+            .unwrap_or_default()
+            .to_string();
+            if token(*at) == Some("(") {
+                let mut depth = 0;
+                while let Some(token) = token(*at) {
+                    *at += 1;
+                    match token {
+                        "(" => depth += 1,
+                        ")" if depth == 1 => break,
+                        ")" => depth -= 1,
+                        _ => {}
+                    }
+                }
+            }
+            let head = source[start..tokens[*at - 1].1].to_string();
+            let children = match token(*at) {
+                Some("{") => {
+                    *at += 1;
+                    let children = Self::parse_tokens(source, tokens, at);
+                    // the closing brace
+                    *at += 1;
+                    Some(children)
+                }
+                _ => None,
+            };
+            selections.push(Selection {
+                name,
+                head,
+                selections: children,
+            });
+        }
+        selections
+    }
+
+    /// `selections` without the `missing` fields, and without any selection that is left with
+    /// nothing to select
+    fn without(selections: Vec<Selection>, missing: &HashSet<String>) -> Vec<Selection> {
+        selections
+            .into_iter()
+            .filter(|selection| !missing.contains(&selection.name))
+            .filter_map(|selection| match selection.selections {
+                None => Some(selection),
+                Some(children) => {
+                    let children = Self::without(children, missing);
+                    match children.is_empty() {
+                        true => None,
+                        false => Some(Selection {
+                            selections: Some(children),
+                            ..selection
+                        }),
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.head)?;
+        if let Some(selections) = &self.selections {
+            write!(f, " {{")?;
+            for selection in selections {
+                write!(f, " {selection}")?;
+            }
+            write!(f, " }}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `fields` without the `missing` ones, one selection per line
+fn selections_without(fields: &[&str], missing: &HashSet<String>) -> Vec<String> {
+    let selections = fields
+        .iter()
+        .flat_map(|field| Selection::parse(field))
+        .collect::<Vec<Selection>>();
+    Selection::without(selections, missing)
+        .iter()
+        .map(Selection::to_string)
+        .collect()
+}
+
+/// talks to the github graphql api directly, no `gh` binary required
+pub struct GitHub {
+    api_url: String,
+    token: String,
+    owner: String,
+    repo: String,
+    /// fields the server said it doesn't have, they are left out of every later query
+    missing_fields: Mutex<HashSet<String>>,
+}
+
+impl GitHub {
+    /// `api_url` is the graphql endpoint, the token is looked up for `host`
+    pub fn new(host: &str, api_url: &str, owner: &str, repo: &str) -> Result<Self, ForgeError> {
+        let token = github_token(host).ok_or_else(|| {
+            ForgeError::Auth(format!(
+                "no token for {host}, set GH_TOKEN or log in with `gh auth login`"
+            ))
+        })?;
+        Ok(Self {
+            api_url: api_url.to_string(),
+            token,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            missing_fields: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// the data and errors of a graphql request, graphql can return both at once
+    fn graphql_with_errors(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<(Value, Vec<Value>), ForgeError> {
+        let request = http::request("POST", &self.api_url)
+            .set("Authorization", &format!("bearer {}", self.token));
+        let mut body = http::send(
+            request,
+            Some(json!({ "query": query, "variables": variables })),
+        )?;
+        let errors = match body["errors"].take() {
+            Value::Array(errors) => errors,
+            _ => vec![],
+        };
+        Ok((body["data"].take(), errors))
+    }
+
+    fn graphql(&self, query: &str, variables: Value) -> Result<Value, ForgeError> {
+        match self.graphql_with_errors(query, variables)? {
+            (data, errors) if errors.is_empty() => Ok(data),
+            (_, errors) => Err(to_request_error(&errors)),
+        }
+    }
+
+    /// the pr fragment, and the review fragment it uses, without any of the fields the server is
+    /// missing
+    fn pr_fragment(&self) -> String {
+        let missing = self.missing_fields.lock().unwrap();
+        let fields = selections_without(PR_FIELDS, &missing);
+        let review = match fields.iter().any(|selection| selection.contains("...review")) {
+            true => format!(
+                "fragment review on PullRequestReview {{\n  {}\n}}\n",
+                selections_without(REVIEW_FIELDS, &missing).join("\n  ")
+            ),
+            false => String::new(),
+        };
+        format!(
+            "{review}fragment pr on PullRequest {{\n  {}\n}}",
+            fields.join("\n  ")
+        )
+    }
+
+    /// remember the fields an `undefinedField` error is about, true if all of the errors are
+    /// about fields we select and at least one of them is news, otherwise leaving them out again
+    /// won't get a different answer
+    fn drop_missing_fields(&self, errors: &[Value]) -> bool {
+        let undefined = errors
+            .iter()
+            .filter(|e| e["extensions"]["code"] == "undefinedField")
+            .filter_map(|e| e["extensions"]["fieldName"].as_str())
+            .filter(|field| {
+                PR_FIELDS
+                    .iter()
+                    .chain(REVIEW_FIELDS)
+                    .any(|selection| selects(selection, field))
+            })
+            .collect::<Vec<&str>>();
+        if undefined.len() < errors.len() {
+            return false
+        }
+        let mut missing = self.missing_fields.lock().unwrap();
+        let mut dropped = false;
+        for field in undefined {
+            if missing.insert(field.to_string()) {
+                warn!(
+                    "{} does not support `{field}`, leaving it out",
+                    self.api_url
+                );
+                dropped = true;
+            }
+        }
+        dropped
+    }
+
+    /// run a query that uses the `pr` fragment, leaving out whatever fields the server is missing,
+    /// as long as that leaves the pr's checks in, without them the watch would pass on nothing
+    fn pr_query(&self, query: &str, variables: Value) -> Result<Value, ForgeError> {
+        loop {
+            let fragment = self.pr_fragment();
+            if !selects(&fragment, "contexts") {
+                return Err(ForgeError::Unsupported("the status checks of a pr"))
+            }
+            let query = format!("{query}\n{fragment}");
+            let (data, errors) = self.graphql_with_errors(&query, variables.clone())?;
+            match errors.is_empty() {
+                true => return Ok(data),
+                false if self.drop_missing_fields(&errors) => continue,
+                false => return Err(to_request_error(&errors)),
+            }
+        }
+    }
+
+    /// the ids of the repository and of everything `new_pr` names, looked up in one query before
+    /// the pr is opened, so a typo doesn't leave one behind that is only half set up
+    fn resolve(&self, new_pr: &NewPr) -> Result<Resolved, ForgeError> {
+        let (users, teams) = new_pr.reviewers();
+        // graphql string literals are json string literals
+        let quote = |name: &str| Value::String(name.to_string()).to_string();
+        let mut query = vec![];
+        let logins = users
+            .iter()
+            .copied()
+            .chain(new_pr.assignees.iter().map(String::as_str))
+            .collect::<Vec<&str>>();
+        for (i, login) in logins.iter().enumerate() {
+            query.push(format!("u{i}: user(login: {}) {{ id }}", quote(login)));
+        }
+        for (i, (org, team)) in teams.iter().enumerate() {
+            query.push(format!(
+                "t{i}: organization(login: {}) {{ team(slug: {}) {{ id }} }}",
+                quote(org),
+                quote(team)
+            ));
+        }
+        let mut repository = vec!["id".to_string()];
+        for (i, label) in new_pr.labels.iter().enumerate() {
+            repository.push(format!("l{i}: label(name: {}) {{ id }}", quote(label)));
+        }
+        if let Some(milestone) = &new_pr.milestone {
+            repository.push(format!(
+                "milestones(query: {}, first: 20) {{ nodes {{ id title }} }}",
+                quote(milestone)
+            ));
+        }
+        query.push(format!(
+            "repository(owner: {}, name: {}) {{ {} }}",
+            quote(&self.owner),
+            quote(&self.repo),
+            repository.join(" ")
+        ));
+        let mut data = self.graphql(&format!("query {{ {} }}", query.join("\n")), json!({}))?;
+
+        let id = |value: &Value, what: &str| {
+            value["id"]
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| ForgeError::Request(format!("no {what}")))
+        };
+        let user_ids = (0..logins.len())
+            .map(|i| id(&data[format!("u{i}")], &format!("user {}", logins[i])))
+            .collect::<Result<Vec<String>, ForgeError>>()?;
+        let (reviewer_ids, assignee_ids) = user_ids.split_at(users.len());
+        let milestone_id = match &new_pr.milestone {
+            Some(title) => {
+                let milestones = nodes(&mut data["repository"]["milestones"]);
+                let milestone = milestones
+                    .iter()
+                    .find(|milestone| milestone["title"] == title.as_str())
+                    .unwrap_or(&Value::Null);
+                Some(id(milestone, &format!("milestone {title}"))?)
+            }
+            None => None,
+        };
+        Ok(Resolved {
+            repository_id: id(
+                &data["repository"],
+                &format!("repository {}/{}", self.owner, self.repo),
+            )?,
+            reviewer_ids: reviewer_ids.to_vec(),
+            team_ids: teams
+                .iter()
+                .enumerate()
+                .map(|(i, (org, team))| {
+                    id(
+                        &data[format!("t{i}")]["team"],
+                        &format!("team {org}/{team}"),
+                    )
+                })
+                .collect::<Result<Vec<String>, ForgeError>>()?,
+            assignee_ids: assignee_ids.to_vec(),
+            label_ids: new_pr
+                .labels
+                .iter()
+                .enumerate()
+                .map(|(i, label)| {
+                    id(
+                        &data["repository"][format!("l{i}")],
+                        &format!("label {label}"),
+                    )
+                })
+                .collect::<Result<Vec<String>, ForgeError>>()?,
+            milestone_id,
+        })
+    }
+
+    /// request the reviews and set the assignees, labels and milestone of the new pr `id`, in a
+    /// single request
+    fn assign(&self, id: &str, resolved: &Resolved) -> Result<(), ForgeError> {
+        let mut mutations = vec![];
+        let mut variables = Map::new();
+        if !resolved.assignee_ids.is_empty()
+            || !resolved.label_ids.is_empty()
+            || resolved.milestone_id.is_some()
+        {
+            let mut update = json!({ "pullRequestId": id });
+            if !resolved.assignee_ids.is_empty() {
+                update["assigneeIds"] = json!(resolved.assignee_ids);
+            }
+            if !resolved.label_ids.is_empty() {
+                update["labelIds"] = json!(resolved.label_ids);
+            }
+            if let Some(milestone_id) = &resolved.milestone_id {
+                update["milestoneId"] = json!(milestone_id);
+            }
+            mutations.push("updatePullRequest(input: $update) { clientMutationId }");
+            variables.insert("update".to_string(), update);
+        }
+        if !resolved.reviewer_ids.is_empty() || !resolved.team_ids.is_empty() {
+            mutations.push("requestReviews(input: $reviews) { clientMutationId }");
+            variables.insert(
+                "reviews".to_string(),
+                json!({
+                    "pullRequestId": id,
+                    "userIds": resolved.reviewer_ids,
+                    "teamIds": resolved.team_ids,
+                }),
+            );
+        }
+        if mutations.is_empty() {
+            return Ok(())
+        }
+        let params = variables
+            .keys()
+            .map(|name| match name.as_str() {
+                "update" => "$update: UpdatePullRequestInput!",
+                _ => "$reviews: RequestReviewsInput!",
+            })
+            .collect::<Vec<&str>>();
+        self.graphql(
+            &format!(
+                "mutation({}) {{ {} }}",
+                params.join(", "),
+                mutations.join("\n")
+            ),
+            Value::Object(variables),
+        )
+        .map(|_| ())
+    }
+}
+
+/// the ids of what a new pr is opened with
+struct Resolved {
+    repository_id: String,
+    reviewer_ids: Vec<String>,
+    team_ids: Vec<String>,
+    assignee_ids: Vec<String>,
+    label_ids: Vec<String>,
+    milestone_id: Option<String>,
+}
+
+impl Forge for GitHub {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        // prs can only be filtered on the branch name, forks are told apart afterwards, and only
+        // open ones count so a branch name used again after a merge gets a pr of its own
+        let (owner, branch) = split_head(head);
+        let mut data = self.pr_query(
+            "query($owner: String!, $repo: String!, $head: String!) {
+              repository(owner: $owner, name: $repo) {
+                pullRequests(headRefName: $head, states: [OPEN], first: 20, orderBy: {field: \
+             CREATED_AT, direction: DESC}) {
+                  nodes { ...pr }
+                }
+              }
+            }",
+            json!({ "owner": self.owner, "repo": self.repo, "head": branch }),
+        )?;
+        for pr in nodes(&mut data["repository"]["pullRequests"]) {
+            let pr: PrInfo = from_value(to_gh_json(pr))?;
+            if is_from(&pr, owner) {
+                return Ok(Some(pr))
+            }
+        }
+        Ok(None)
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        let mut data = self.graphql(
+            "query($owner: String!, $repo: String!, $oid: GitObjectID!) {
+              repository(owner: $owner, name: $repo) {
+                object(oid: $oid) {
+                  ... on Commit {
+                    associatedPullRequests(first: 20) {
+                      nodes {
+                        state headRefName headRefOid isCrossRepository
+                        headRepositoryOwner { login }
+                      }
+                    }
+                  }
+                }
+              }
+            }",
+            json!({ "owner": self.owner, "repo": self.repo, "oid": sha }),
+        )?;
+        let found = nodes(&mut data["repository"]["object"]["associatedPullRequests"])
+            .into_iter()
+            .filter(|pr| pr["state"] == "OPEN")
+            .map(from_value)
+            .collect::<Result<Vec<PrInfo>, _>>()?;
+        let found = found
+            .iter()
+            .find(|pr| pr.headRefOid == sha)
+            .or(found.first());
+        match found {
+            Some(pr) => self.fetch_pr(&pr.head()),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        let mut data = self.pr_query(
+            "query($owner: String!, $repo: String!, $number: Int!) {
+              repository(owner: $owner, name: $repo) {
+                pullRequest(number: $number) { ...pr }
+              }
+            }",
+            json!({ "owner": self.owner, "repo": self.repo, "number": number }),
+        )?;
+        match data["repository"]["pullRequest"].take() {
+            Value::Null => Ok(None),
+            pr => Ok(Some(from_value(to_gh_json(pr))?)),
+        }
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        let resolved = self.resolve(new_pr)?;
+        let mutation = "mutation($input: CreatePullRequestInput!) { createPullRequest(input: \
+                        $input) { pullRequest { id number } } }";
+        let mut input = json!({
+            "repositoryId": resolved.repository_id,
+            "baseRefName": new_pr.base,
+            "headRefName": new_pr.head,
+            "title": new_pr.title,
+            "body": new_pr.body,
+            "draft": new_pr.draft,
+        });
+        let created = match self.graphql(mutation, json!({ "input": input })) {
+            // old enterprise servers predate draft prs
+            Err(ForgeError::Request(message)) if message.contains("draft") => {
+                warn!(
+                    "{} does not support draft prs, opening a ready one",
+                    self.api_url
+                );
+                input.as_object_mut().map(|input| input.remove("draft"));
+                self.graphql(mutation, json!({ "input": input }))?
+            }
+            result => result?,
+        };
+        let created = &created["createPullRequest"]["pullRequest"];
+        let number = created["number"].as_u64().map(|number| number as u32);
+        let (Some(id), Some(number)) = (created["id"].as_str(), number) else {
+            return Err(ForgeError::Request(format!(
+                "{} opened a pr for {} but didn't say which, look for it before trying again",
+                self.api_url, new_pr.head
+            )))
+        };
+        // the pr is there either way, failing to set it up isn't worth failing over
+        if let Err(e) = self.assign(id, &resolved) {
+            warn!(
+                "opened #{number} but could not set its reviewers, assignees, labels or \
+                 milestone: {e}"
+            );
+        }
+        self.fetch_pr_by_number(number)?.ok_or_else(|| {
+            ForgeError::Request(format!("no pr #{number} found after opening it"))
+        })
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        self.graphql(
+            "mutation($input: AddCommentInput!) { addComment(input: $input) { clientMutationId } }",
+            json!({ "input": { "subjectId": pr.id, "body": body } }),
+        )
+        .map(|_| ())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        let method = match method {
+            MergeMethod::Merge => "MERGE",
+            MergeMethod::Rebase => "REBASE",
+            MergeMethod::Squash => "SQUASH",
+        };
+        self.graphql(
+            "mutation($input: MergePullRequestInput!) { mergePullRequest(input: $input) { \
+             clientMutationId } }",
+            json!({ "input": { "pullRequestId": pr.id, "mergeMethod": method } }),
+        )
+        .map(|_| ())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        let data = self.graphql(
+            "query($owner: String!, $repo: String!) { repository(owner: $owner, name: $repo) { \
+             defaultBranchRef { name } } }",
+            json!({ "owner": self.owner, "repo": self.repo }),
+        )?;
+        data["repository"]["defaultBranchRef"]["name"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| {
+                ForgeError::Request(format!(
+                    "no default branch for {}/{}",
+                    self.owner, self.repo
+                ))
+            })
+    }
+
+    fn viewer(&self) -> Result<Option<String>, ForgeError> {
+        let data = self.graphql("query { viewer { login } }", json!({}))?;
+        Ok(data["viewer"]["login"].as_str().map(String::from))
+    }
+}
+
+fn to_request_error(errors: &[Value]) -> ForgeError {
+    let messages = errors
+        .iter()
+        .filter_map(|e| e["message"].as_str())
+        .collect::<Vec<&str>>();
+    ForgeError::Request(messages.join("; "))
+}
+
+/// pull `nodes` out of a graphql connection
+fn nodes(connection: &mut Value) -> Vec<Value> {
+    match connection["nodes"].take() {
+        Value::Array(nodes) => nodes,
+        _ => vec![],
+    }
+}
+
+/// a string field that graphql may hand back as null
+fn string(value: &Value) -> Value {
+    Value::String(value.as_str().unwrap_or_default().to_string())
+}
+
+/// reshape a graphql pull request into the json that `gh pr list --json` emits, which is what
+/// the models are shaped after
+fn to_gh_json(mut pr: Value) -> Value {
+    for field in ["labels", "files", "latestReviews", "reviews", "comments"] {
+        let mut items = nodes(&mut pr[field]);
+        for item in items.iter_mut() {
+            if let Value::Object(item) = item {
+                item.entry("reactionGroups").or_insert(json!([]));
+                for nullable in ["description", "minimizedReason", "submittedAt"] {
+                    if item.contains_key(nullable) {
+                        item[nullable] = string(&item[nullable]);
+                    }
+                }
+            }
+        }
+        pr[field] = Value::Array(items);
+    }
+
+    pr["assignees"] = nodes(&mut pr["assignees"])
+        .iter()
+        .map(|user| user["login"].clone())
+        .collect();
+    pr["reviewRequests"] = nodes(&mut pr["reviewRequests"])
+        .iter()
+        .map(|request| {
+            let reviewer = &request["requestedReviewer"];
+            match reviewer["login"].is_string() {
+                true => reviewer["login"].clone(),
+                false => reviewer["slug"].clone(),
+            }
+        })
+        .collect();
+    pr["milestone"] = pr["milestone"]["title"].clone();
+
+    pr["commits"] = nodes(&mut pr["commits"])
+        .iter_mut()
+        .map(|node| {
+            let mut commit = node["commit"].take();
+            commit["authors"] = nodes(&mut commit["authors"])
+                .iter()
+                .map(|author| {
+                    json!({
+                        "login": string(&author["user"]["login"]),
+                        "id": author["user"]["id"],
+                        "name": author["name"],
+                        "email": author["email"],
+                    })
+                })
+                .collect();
+            commit
+        })
+        .collect();
+
+    let mut last_commit = nodes(&mut pr["lastCommit"]);
+    pr.as_object_mut().map(|pr| pr.remove("lastCommit"));
+    let contexts = last_commit
+        .first_mut()
+        .map(|node| nodes(&mut node["commit"]["statusCheckRollup"]["contexts"]))
+        .unwrap_or_default();
+    pr["statusCheckRollup"] = contexts.into_iter().map(to_gh_check).collect();
+
+    for field in ["projectCards", "reactionGroups"] {
+        pr[field] = json!([]);
+    }
+    for field in ["mergeStateStatus", "mergeable", "reviewDecision"] {
+        pr[field] = string(&pr[field]);
+    }
+    pr
+}
+
+fn to_gh_check(check: Value) -> Value {
+    let mut gh_check = Map::new();
+    gh_check.insert("__typename".to_string(), check["__typename"].clone());
+    match check["__typename"].as_str() {
+        Some("CheckRun") => {
+            for field in [
+                "name",
+                "startedAt",
+                "completedAt",
+                "detailsUrl",
+                "conclusion",
+            ] {
+                gh_check.insert(field.to_string(), string(&check[field]));
+            }
+            gh_check.insert("status".to_string(), check["status"].clone());
+            gh_check.insert(
+                "workflowName".to_string(),
+                string(&check["checkSuite"]["workflowRun"]["workflow"]["name"]),
+            );
+        }
+        _ => {
+            for field in ["context", "targetUrl"] {
+                gh_check.insert(field.to_string(), string(&check[field]));
+            }
+            gh_check.insert("state".to_string(), check["state"].clone());
+            gh_check.insert("startedAt".to_string(), string(&check["createdAt"]));
+        }
+    }
+    Value::Object(gh_check)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::forge::http::stand_in::StandIn;
+
+    fn github(api_url: &str) -> GitHub {
+        GitHub {
+            api_url: api_url.to_string(),
+            token: "t0ken".to_string(),
+            owner: "octo".to_string(),
+            repo: "uploader".to_string(),
+            missing_fields: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn fixture_pr() -> Value {
+        serde_json::from_str(include_str!("mock_data/github_pr.json")).unwrap()
+    }
+
+    #[test]
+    fn test_to_gh_json() {
+        let pr_info: PrInfo = from_value(to_gh_json(fixture_pr())).unwrap();
+        assert_eq!(pr_info.number, 42);
+        assert_eq!(pr_info.headRefName, "feature/retry-uploads");
+        assert_eq!(pr_info.assignees, vec!["alice"]);
+        assert_eq!(pr_info.reviewRequests, vec!["bob", "uploaders"]);
+        assert_eq!(pr_info.milestone.as_deref(), Some("v2"));
+        assert_eq!(pr_info.labels[0].description, "");
+        assert_eq!(pr_info.latestReviews[0].author.login, "carol");
+        assert_eq!(pr_info.commits[0].authors[0].login, "alice");
+        assert_eq!(pr_info.sha(), "b7e23ec29af22b0b4e41da31e868d57226121c84");
+        let checks = pr_info
+            .statusCheckRollup
+            .iter()
+            .map(|check| (check.name(), check.short_status_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            checks,
+            vec![
+                ("test".to_string(), " OK ".to_string()),
+                ("deploy/preview".to_string(), " .. ".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fetch_pr_only_open() {
+        let response = json!({
+            "data": { "repository": { "pullRequests": { "nodes": [fixture_pr()] } } }
+        })
+        .to_string();
+        let server = StandIn::serve(&[(200, &response)]);
+        let pr_info = github(&server.url)
+            .fetch_pr("feature/retry-uploads")
+            .unwrap()
+            .unwrap();
+        assert_eq!(pr_info.number, 42);
+        let requests = server.requests();
+        assert!(requests[0].starts_with("POST / "), "{}", requests[0]);
+        assert!(requests[0].contains("states: [OPEN]"), "{}", requests[0]);
+    }
+
+    fn new_pr() -> NewPr {
+        NewPr {
+            title: "Retry uploads".to_string(),
+            body: String::new(),
+            head: "feature/retry-uploads".to_string(),
+            base: "main".to_string(),
+            draft: false,
+            reviewers: vec!["bob".to_string()],
+            assignees: vec![],
+            labels: vec![],
+            milestone: None,
+        }
+    }
+
+    #[test]
+    fn test_create_pr_outlives_assigning() {
+        let resolved = json!({ "data": { "u0": { "id": "U_2" }, "repository": { "id": "R_1" } } });
+        let created = json!({
+            "data": { "createPullRequest": { "pullRequest": { "id": "PR_1", "number": 42 } } }
+        });
+        let refused = json!({ "errors": [{ "message": "bob can't review this pr" }] });
+        let fetched = json!({ "data": { "repository": { "pullRequest": fixture_pr() } } });
+        let server = StandIn::serve(&[
+            (200, &resolved.to_string()),
+            (200, &created.to_string()),
+            (200, &refused.to_string()),
+            (200, &fetched.to_string()),
+        ]);
+        let pr_info = github(&server.url).create_pr(&new_pr()).unwrap();
+        assert_eq!(pr_info.number, 42);
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_create_pr_without_an_id() {
+        let resolved = json!({ "data": { "u0": { "id": "U_2" }, "repository": { "id": "R_1" } } });
+        let created = json!({ "data": { "createPullRequest": { "pullRequest": null } } });
+        let server = StandIn::serve(&[(200, &resolved.to_string()), (200, &created.to_string())]);
+        let result = github(&server.url).create_pr(&new_pr());
+        assert!(matches!(result, Err(ForgeError::Request(_))), "{result:?}");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_pr_fragment_leaves_out_nested_fields() {
+        let github = github("http://127.0.0.1:1");
+        github.missing_fields.lock().unwrap().extend([
+            "workflowRun".to_string(),
+            "includesCreatedEdit".to_string(),
+        ]);
+        let fragment = github.pr_fragment();
+        for kept in ["lastCommit: commits(last: 1)", "contexts", "detailsUrl", "comments(first"] {
+            assert!(fragment.contains(kept), "{kept} in {fragment}");
+        }
+        for dropped in ["checkSuite", "workflow", "includesCreatedEdit"] {
+            assert!(!selects(&fragment, dropped), "{dropped} in {fragment}");
+        }
+        let review = "fragment review on PullRequestReview {\n  id\n  author { login }\n  \
+                      authorAssociation\n";
+        assert!(fragment.starts_with(review), "{fragment}");
+    }
+
+    fn undefined_field(field: &str) -> String {
+        json!({
+            "errors": [{
+                "message": format!("Field '{field}' doesn't exist on type 'PullRequest'"),
+                "extensions": { "code": "undefinedField", "fieldName": field },
+            }]
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_pr_query_gives_up_on_the_same_field() {
+        let response = undefined_field("authorAssociation");
+        let server = StandIn::serve(&[(200, &response), (200, &response)]);
+        let result = github(&server.url).fetch_pr_by_number(42);
+        assert!(result.is_err(), "{result:?}");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_pr_query_needs_the_checks() {
+        let response = undefined_field("statusCheckRollup");
+        let server = StandIn::serve(&[(200, &response)]);
+        let result = github(&server.url).fetch_pr_by_number(42);
+        assert!(
+            matches!(result, Err(ForgeError::Unsupported(_))),
+            "{result:?}"
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,639 @@
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use super::{found, http, split_head, token::gitlab_token, Forge, ForgeError, MergeMethod, NewPr};
+use crate::prinfo::{
+    CheckConclusionState, CheckStatusState, Commit, File, Label, Node, PrInfo, Repo, Review,
+    StatusCheck, User,
+};
+
+/// how many items a page of a list holds, gitlab's cap on `per_page`
+const PAGE_SIZE: usize = 100;
+
+/// talks to the gitlab v4 rest api, merge requests are mapped onto `PrInfo`
+pub struct GitLab {
+    api_url: String,
+    token: String,
+    project: String,
+}
+
+#[derive(Deserialize)]
+struct GlUser {
+    id: u64,
+    username: String,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Project {
+    default_branch: String,
+}
+
+#[derive(Deserialize)]
+struct Milestone {
+    id: Option<u64>,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct Pipeline {
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct MergeRequest {
+    id: u64,
+    iid: u32,
+    title: String,
+    description: Option<String>,
+    state: String,
+    #[serde(default)]
+    draft: bool,
+    web_url: String,
+    author: GlUser,
+    #[serde(default)]
+    assignees: Vec<GlUser>,
+    #[serde(default)]
+    reviewers: Vec<GlUser>,
+    #[serde(default)]
+    labels: Vec<String>,
+    milestone: Option<Milestone>,
+    source_branch: String,
+    target_branch: String,
+    source_project_id: u64,
+    target_project_id: u64,
+    sha: Option<String>,
+    merge_commit_sha: Option<String>,
+    merged_by: Option<GlUser>,
+    created_at: String,
+    updated_at: String,
+    merged_at: Option<String>,
+    closed_at: Option<String>,
+    #[serde(default)]
+    has_conflicts: bool,
+    detailed_merge_status: Option<String>,
+    head_pipeline: Option<Pipeline>,
+}
+
+#[derive(Deserialize)]
+struct Job {
+    name: String,
+    stage: String,
+    status: String,
+    web_url: String,
+    started_at: Option<String>,
+    finished_at: Option<String>,
+    #[serde(default)]
+    allow_failure: bool,
+}
+
+#[derive(Deserialize)]
+struct GlCommit {
+    id: String,
+    title: String,
+    message: String,
+    author_name: String,
+    author_email: String,
+    authored_date: String,
+    committed_date: String,
+}
+
+#[derive(Deserialize)]
+struct Diff {
+    new_path: String,
+    diff: String,
+}
+
+#[derive(Deserialize)]
+struct Approver {
+    user: GlUser,
+}
+
+#[derive(Deserialize, Default)]
+struct Approvals {
+    #[serde(default)]
+    approved: bool,
+    #[serde(default)]
+    approved_by: Vec<Approver>,
+}
+
+impl GitLab {
+    /// `api_url` is the v4 api root, e.g. `https://gitlab.com/api/v4`, `project` is the full
+    /// path of the project including any subgroups
+    pub fn new(host: &str, api_url: &str, project: &str) -> Result<Self, ForgeError> {
+        let token = gitlab_token(host).ok_or_else(|| {
+            ForgeError::Auth(format!(
+                "no token for {host}, set GITLAB_TOKEN or log in with `glab auth login`"
+            ))
+        })?;
+        Ok(Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            token,
+            project: http::encode(project),
+        })
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let url = format!("{}/projects/{}{path}", self.api_url, self.project);
+        http::request(method, &url).set("PRIVATE-TOKEN", &self.token)
+    }
+
+    fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, ForgeError> {
+        let request = query
+            .iter()
+            .fold(self.request("GET", path), |request, (k, v)| {
+                request.query(k, v)
+            });
+        Ok(serde_json::from_value(http::send(request, None)?)?)
+    }
+
+    /// every item of a list, a page at a time until one comes back short
+    fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, ForgeError> {
+        let mut all = vec![];
+        for page in 1.. {
+            let (per_page, page) = (PAGE_SIZE.to_string(), page.to_string());
+            let mut query = query.to_vec();
+            query.extend([("per_page", per_page.as_str()), ("page", page.as_str())]);
+            let items: Vec<T> = self.get(path, &query)?;
+            let last = items.len() < PAGE_SIZE;
+            all.extend(items);
+            if last {
+                break
+            }
+        }
+        Ok(all)
+    }
+
+    /// merge request `iid` with its pipeline jobs, commits, diffs and approvals
+    fn fetch_mr(&self, iid: u32) -> Result<PrInfo, ForgeError> {
+        let path = format!("/merge_requests/{iid}");
+        let mr: MergeRequest = self.get(&path, &[])?;
+        let jobs: Vec<Job> = match &mr.head_pipeline {
+            Some(pipeline) => self.get_all(&format!("/pipelines/{}/jobs", pipeline.id), &[])?,
+            None => vec![],
+        };
+        let commits: Vec<GlCommit> = self.get_all(&format!("{path}/commits"), &[])?;
+        // diffs is gitlab 15.7+, older servers just don't get a file list
+        let diffs: Vec<Diff> = self
+            .get_all(&format!("{path}/diffs"), &[])
+            .unwrap_or_default();
+        let approvals: Approvals = self
+            .get(&format!("{path}/approvals"), &[])
+            .unwrap_or_default();
+
+        Ok(to_pr_info(mr, jobs, commits, diffs, approvals))
+    }
+
+    /// the id of the user called `username`
+    fn user_id(&self, username: &str) -> Result<u64, ForgeError> {
+        let request = http::request("GET", &format!("{}/users", self.api_url))
+            .set("PRIVATE-TOKEN", &self.token)
+            .query("username", username);
+        let users: Vec<GlUser> = serde_json::from_value(http::send(request, None)?)?;
+        users
+            .first()
+            .map(|user| user.id)
+            .ok_or_else(|| ForgeError::Request(format!("no gitlab user {username}")))
+    }
+
+    /// the id of the project's milestone called `title`
+    fn milestone_id(&self, title: &str) -> Result<u64, ForgeError> {
+        let milestones: Vec<Milestone> = self.get(
+            "/milestones",
+            &[("title", title), ("include_ancestors", "true")],
+        )?;
+        milestones
+            .iter()
+            .find(|milestone| milestone.title == title)
+            .and_then(|milestone| milestone.id)
+            .ok_or_else(|| ForgeError::Request(format!("no milestone {title}")))
+    }
+
+    fn mr_path(pr: &PrInfo) -> String {
+        format!("/merge_requests/{}", pr.number)
+    }
+}
+
+impl Forge for GitLab {
+    fn name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError> {
+        // forks aren't told apart, the source branch is all there is to go on
+        let (_, branch) = split_head(head);
+        let found: Vec<MergeRequest> = self.get(
+            "/merge_requests",
+            &[("source_branch", branch), ("state", "opened")],
+        )?;
+        match found.into_iter().next() {
+            Some(found) => self.fetch_mr(found.iid).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        let found: Vec<MergeRequest> =
+            self.get(&format!("/repository/commits/{sha}/merge_requests"), &[])?;
+        let mut found = found.into_iter().filter(|mr| mr.state == "opened");
+        match found.next() {
+            Some(mr) => self.fetch_mr(mr.iid).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        found(self.fetch_mr(number))
+    }
+
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError> {
+        if let (Some(_), _) = split_head(&new_pr.head) {
+            return Err(ForgeError::Unsupported(
+                "merge requests from forks on gitlab",
+            ))
+        }
+        let (reviewers, teams) = new_pr.reviewers();
+        if !teams.is_empty() {
+            return Err(ForgeError::Unsupported("team reviewers on gitlab"))
+        }
+        // everything is looked up before the merge request is opened, so a typo doesn't leave
+        // one behind that is only half set up
+        let reviewer_ids = reviewers
+            .iter()
+            .map(|username| self.user_id(username))
+            .collect::<Result<Vec<u64>, ForgeError>>()?;
+        let assignee_ids = new_pr
+            .assignees
+            .iter()
+            .map(|username| self.user_id(username))
+            .collect::<Result<Vec<u64>, ForgeError>>()?;
+        let milestone_id = match &new_pr.milestone {
+            Some(title) => Some(self.milestone_id(title)?),
+            None => None,
+        };
+        let title = match new_pr.draft {
+            true => format!("Draft: {}", new_pr.title),
+            false => new_pr.title.clone(),
+        };
+        http::send(
+            self.request("POST", "/merge_requests"),
+            Some(json!({
+                "source_branch": new_pr.head,
+                "target_branch": new_pr.base,
+                "title": title,
+                "description": new_pr.body,
+                "reviewer_ids": reviewer_ids,
+                "assignee_ids": assignee_ids,
+                "labels": new_pr.labels.join(","),
+                "milestone_id": milestone_id,
+            })),
+        )?;
+        self.fetch_pr(&new_pr.head)?.ok_or_else(|| {
+            ForgeError::Request(format!(
+                "no merge request found for {} after creating it",
+                new_pr.head
+            ))
+        })
+    }
+
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError> {
+        let path = format!("{}/notes", Self::mr_path(pr));
+        http::send(self.request("POST", &path), Some(json!({ "body": body }))).map(|_| ())
+    }
+
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError> {
+        let squash = match method {
+            MergeMethod::Merge => false,
+            MergeMethod::Squash => true,
+            MergeMethod::Rebase => return Err(ForgeError::Unsupported("rebase merges on gitlab")),
+        };
+        let path = format!("{}/merge", Self::mr_path(pr));
+        http::send(
+            self.request("PUT", &path),
+            Some(json!({ "squash": squash })),
+        )
+        .map(|_| ())
+    }
+
+    fn default_branch(&self) -> Result<String, ForgeError> {
+        let project: Project = self.get("", &[])?;
+        Ok(project.default_branch)
+    }
+
+    fn viewer(&self) -> Result<Option<String>, ForgeError> {
+        let request = http::request("GET", &format!("{}/user", self.api_url))
+            .set("PRIVATE-TOKEN", &self.token);
+        let user: GlUser = serde_json::from_value(http::send(request, None)?)?;
+        Ok(Some(user.username))
+    }
+}
+
+fn to_user(user: GlUser) -> User {
+    User {
+        login: user.username,
+        id: Some(user.id.to_string()),
+        name: user.name,
+        email: None,
+    }
+}
+
+fn to_state(state: &str) -> String {
+    match state {
+        "opened" => "OPEN",
+        "merged" => "MERGED",
+        _ => "CLOSED",
+    }
+    .to_string()
+}
+
+/// pipeline jobs map onto check runs, the stage stands in for the workflow
+fn to_check(job: Job) -> StatusCheck {
+    let (status, conclusion) = match job.status.as_str() {
+        "created" | "waiting_for_resource" | "preparing" => (CheckStatusState::Queued, None),
+        "pending" => (CheckStatusState::Pending, None),
+        "scheduled" => (CheckStatusState::Waiting, None),
+        "running" => (CheckStatusState::InProgress, None),
+        "success" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Success),
+        ),
+        "failed" if job.allow_failure => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Neutral),
+        ),
+        "failed" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Failure),
+        ),
+        "canceled" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Cancelled),
+        ),
+        // manual jobs never finish on their own, don't let them hold up the watch
+        "skipped" | "manual" => (
+            CheckStatusState::Completed,
+            Some(CheckConclusionState::Skipped),
+        ),
+        _ => (CheckStatusState::Pending, None),
+    };
+    StatusCheck::CheckRun {
+        completedAt: job.finished_at.unwrap_or_default(),
+        conclusion,
+        detailsUrl: job.web_url,
+        name: job.name,
+        startedAt: job.started_at.unwrap_or_default(),
+        status,
+        workflowName: job.stage,
+    }
+}
+
+fn to_commit(commit: GlCommit) -> Commit {
+    let body = commit
+        .message
+        .split_once('\n')
+        .map(|(_, body)| body.trim().to_string())
+        .unwrap_or_default();
+    Commit {
+        authoredDate: commit.authored_date,
+        authors: vec![User {
+            login: commit.author_name.clone(),
+            email: Some(commit.author_email),
+            id: None,
+            name: Some(commit.author_name),
+        }],
+        committedDate: commit.committed_date,
+        messageBody: body,
+        messageHeadline: commit.title,
+        oid: commit.id,
+    }
+}
+
+fn to_file(diff: Diff) -> File {
+    let count = |prefix: char| {
+        diff.diff
+            .lines()
+            .filter(|line| line.starts_with(prefix))
+            .count()
+    };
+    File {
+        additions: count('+'),
+        deletions: count('-'),
+        path: diff.new_path,
+    }
+}
+
+fn to_pr_info(
+    mr: MergeRequest,
+    jobs: Vec<Job>,
+    commits: Vec<GlCommit>,
+    diffs: Vec<Diff>,
+    approvals: Approvals,
+) -> PrInfo {
+    let files: Vec<File> = diffs.into_iter().map(to_file).collect();
+    let approved_by: Vec<String> = approvals
+        .approved_by
+        .iter()
+        .map(|approver| approver.user.username.clone())
+        .collect();
+    let reviews: Vec<Review> = approvals
+        .approved_by
+        .into_iter()
+        .map(|approver| Review {
+            author: to_user(approver.user),
+            state: "APPROVED".to_string(),
+            ..Default::default()
+        })
+        .collect();
+    // gitlab lists commits newest first, gh oldest first
+    let mut commits: Vec<Commit> = commits.into_iter().map(to_commit).collect();
+    commits.reverse();
+
+    PrInfo {
+        additions: files.iter().map(|f| f.additions as u32).sum(),
+        deletions: files.iter().map(|f| f.deletions as u32).sum(),
+        changedFiles: files.len() as u32,
+        assignees: mr.assignees.into_iter().map(|user| user.username).collect(),
+        author: to_user(mr.author),
+        baseRefName: mr.target_branch,
+        body: mr.description.unwrap_or_default(),
+        closed: mr.state != "opened",
+        closedAt: mr.closed_at,
+        commits,
+        createdAt: mr.created_at,
+        files,
+        headRefName: mr.source_branch,
+        headRefOid: mr.sha.unwrap_or_default(),
+        headRepository: Repo {
+            id: mr.source_project_id.to_string(),
+            ..Default::default()
+        },
+        id: mr.id.to_string(),
+        isCrossRepository: mr.source_project_id != mr.target_project_id,
+        isDraft: mr.draft,
+        labels: mr
+            .labels
+            .into_iter()
+            .map(|name| Label {
+                name,
+                ..Default::default()
+            })
+            .collect(),
+        latestReviews: reviews.clone(),
+        mergeCommit: mr.merge_commit_sha.map(|oid| Node { oid }),
+        mergeStateStatus: mr.detailed_merge_status.unwrap_or_default().to_uppercase(),
+        mergeable: match mr.has_conflicts {
+            true => "CONFLICTING",
+            false => "MERGEABLE",
+        }
+        .to_string(),
+        mergedAt: mr.merged_at,
+        mergedBy: mr.merged_by.map(to_user),
+        milestone: mr.milestone.map(|milestone| milestone.title),
+        number: mr.iid,
+        reviewDecision: match approvals.approved {
+            true => "APPROVED",
+            false => "REVIEW_REQUIRED",
+        }
+        .to_string(),
+        reviewRequests: mr
+            .reviewers
+            .into_iter()
+            .map(|user| user.username)
+            .filter(|username| !approved_by.contains(username))
+            .collect(),
+        reviews,
+        state: to_state(&mr.state),
+        statusCheckRollup: jobs.into_iter().map(to_check).collect(),
+        title: mr.title,
+        updatedAt: mr.updated_at,
+        url: mr.web_url,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::{from_str, Value};
+
+    use super::*;
+    use crate::forge::http::stand_in::StandIn;
+
+    fn fixture_pr_info() -> PrInfo {
+        let mr: MergeRequest = from_str(include_str!("mock_data/gitlab_mr.json")).unwrap();
+        let jobs: Vec<Job> = from_str(include_str!("mock_data/gitlab_jobs.json")).unwrap();
+        let commits: Vec<GlCommit> =
+            from_str(include_str!("mock_data/gitlab_commits.json")).unwrap();
+        let diffs: Vec<Diff> = from_str(include_str!("mock_data/gitlab_diffs.json")).unwrap();
+        let approvals: Approvals =
+            from_str(include_str!("mock_data/gitlab_approvals.json")).unwrap();
+        to_pr_info(mr, jobs, commits, diffs, approvals)
+    }
+
+    #[test]
+    fn test_pr_fields() {
+        let pr_info = fixture_pr_info();
+        assert_eq!(pr_info.number, 7);
+        assert_eq!(pr_info.title, "Retry uploads");
+        assert_eq!(pr_info.state, "OPEN");
+        assert!(pr_info.isDraft);
+        assert_eq!(pr_info.headRefName, "feature/retry-uploads");
+        assert_eq!(pr_info.baseRefName, "main");
+        assert!(!pr_info.isCrossRepository);
+        assert_eq!(pr_info.mergeStateStatus, "NOT_APPROVED");
+        assert_eq!(pr_info.milestone.as_deref(), Some("v2"));
+        assert_eq!(pr_info.labels[0].name, "enhancement");
+        // oldest first, like gh
+        assert_eq!(pr_info.commits[0].messageHeadline, "Retry uploads");
+        assert_eq!(
+            pr_info.commits[1].messageBody,
+            "Wait longer after each failure."
+        );
+        assert_eq!(pr_info.sha(), "b7e23ec29af22b0b4e41da31e868d57226121c84");
+        assert_eq!(
+            (pr_info.files.len(), pr_info.additions, pr_info.deletions),
+            (2, 3, 1)
+        );
+    }
+
+    #[test]
+    fn test_jobs_become_checks() {
+        let pr_info = fixture_pr_info();
+        let summary = pr_info
+            .statusCheckRollup
+            .iter()
+            .map(|check| (check.name(), check.short_status_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("unit".to_string(), " OK ".to_string()),
+                // allowed to fail, so it doesn't fail the pr
+                ("lint".to_string(), "Pass".to_string()),
+                ("integration".to_string(), " .. ".to_string()),
+                // waits on someone to start it, so it doesn't hold up the watch
+                ("deploy".to_string(), "Skip".to_string()),
+            ]
+        );
+        assert!(matches!(
+            &pr_info.statusCheckRollup[1],
+            StatusCheck::CheckRun {
+                conclusion: Some(CheckConclusionState::Neutral),
+                ..
+            }
+        ));
+        assert!(matches!(
+            &pr_info.statusCheckRollup[3],
+            StatusCheck::CheckRun {
+                conclusion: Some(CheckConclusionState::Skipped),
+                ..
+            }
+        ));
+        assert_eq!(pr_info.pending_checks().len(), 1);
+    }
+
+    #[test]
+    fn test_approvals_become_reviews() {
+        let pr_info = fixture_pr_info();
+        let reviews = pr_info
+            .reviews
+            .iter()
+            .map(|review| (review.author.login.as_str(), review.state.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(reviews, vec![("carol", "APPROVED")]);
+        // who already approved isn't still waited on
+        assert_eq!(pr_info.reviewRequests, vec!["bob"]);
+        assert_eq!(pr_info.reviewDecision, "REVIEW_REQUIRED");
+    }
+
+    #[test]
+    fn test_get_all_pages() {
+        let jobs: Vec<Value> = from_str(include_str!("mock_data/gitlab_jobs.json")).unwrap();
+        let job = |id: usize| {
+            let mut job = jobs[0].clone();
+            job["id"] = id.into();
+            job
+        };
+        let full_page = Value::Array((0..PAGE_SIZE).map(job).collect()).to_string();
+        let last_page = Value::Array(vec![job(PAGE_SIZE)]).to_string();
+        let server = StandIn::serve(&[(200, &full_page), (200, &last_page)]);
+        let gitlab = GitLab {
+            api_url: server.url.clone(),
+            token: "t0ken".to_string(),
+            project: "octo/uploader".to_string(),
+        };
+        let jobs: Vec<Job> = gitlab.get_all("/pipelines/7/jobs", &[]).unwrap();
+        assert_eq!(jobs.len(), PAGE_SIZE + 1);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("&page=2"), "{}", requests[1]);
+    }
+}
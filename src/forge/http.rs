@@ -0,0 +1,156 @@
+//! The bits of http every api backend needs.
+use serde_json::Value;
+
+use super::ForgeError;
+
+/// a request with the headers every backend wants
+pub fn request(method: &str, url: &str) -> ureq::Request {
+    ureq::request(method, url)
+        .set("User-Agent", "git-pr-rust")
+        .set("Accept", "application/json")
+}
+
+/// send `request` (with an optional json body) and decode the json it answers with, an empty
+/// answer comes back as `Value::Null`
+pub fn send(request: ureq::Request, body: Option<Value>) -> Result<Value, ForgeError> {
+    let url = request.url().to_string();
+    let response = match body {
+        Some(body) => request.send_json(body),
+        None => request.call(),
+    };
+
+    match response {
+        Ok(response) => {
+            let text = response
+                .into_string()
+                .map_err(|e| ForgeError::Request(format!("{url}: {e}")))?;
+            match text.trim().is_empty() {
+                true => Ok(Value::Null),
+                false => Ok(serde_json::from_str(&text)?),
+            }
+        }
+        Err(ureq::Error::Status(401, _)) => {
+            Err(ForgeError::Auth(format!("{url} rejected the token")))
+        }
+        Err(ureq::Error::Status(404, _)) => Err(ForgeError::NotFound(url)),
+        Err(ureq::Error::Status(code, response)) => {
+            let text = response.into_string().unwrap_or_default();
+            // a 403 is a token without access, unless it is github's rate limit
+            match code == 403 && !text.to_lowercase().contains("rate limit") {
+                true => Err(ForgeError::Auth(format!(
+                    "{url} turned the token down, check it has access to the repo: {text}"
+                ))),
+                false => Err(ForgeError::Request(format!("{url} answered {code}: {text}"))),
+            }
+        }
+        Err(e) => Err(ForgeError::Request(e.to_string())),
+    }
+}
+
+/// percent encode a path segment, e.g. a gitlab project path
+pub fn encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// a local http server for the backends' tests to point their api url at
+#[cfg(test)]
+pub mod stand_in {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    pub struct StandIn {
+        pub url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StandIn {
+        /// answer one request with each of `responses`, a status and a json body, in order
+        pub fn serve(responses: &[(u16, &str)]) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(vec![]));
+            let responses = responses
+                .iter()
+                .map(|(status, body)| (*status, body.to_string()))
+                .collect::<Vec<(u16, String)>>();
+            let recorded = requests.clone();
+            thread::spawn(move || {
+                for (status, body) in responses {
+                    let Ok((stream, _)) = listener.accept() else {
+                        return
+                    };
+                    let mut reader = BufReader::new(stream);
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+                    let mut length = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() {
+                            break
+                        }
+                        if let Some((name, value)) = header.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut request_body = vec![0; length];
+                    reader.read_exact(&mut request_body).unwrap();
+                    recorded.lock().unwrap().push(format!(
+                        "{}\n{}",
+                        request_line.trim(),
+                        String::from_utf8_lossy(&request_body)
+                    ));
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: \
+                         {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                    .unwrap();
+                }
+            });
+            Self { url, requests }
+        }
+
+        /// each request so far, its request line then its body
+        pub fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stand_in::StandIn, *};
+
+    #[test]
+    fn test_send_errors() {
+        let server = StandIn::serve(&[
+            (401, "{}"),
+            (403, r#"{"message": "Resource not accessible by integration"}"#),
+            (403, r#"{"message": "API rate limit exceeded for user"}"#),
+            (404, r#"{"message": "Not Found"}"#),
+            (500, "{}"),
+        ]);
+        let send = || send(request("GET", &server.url), None);
+        assert!(matches!(send(), Err(ForgeError::Auth(_))));
+        assert!(matches!(send(), Err(ForgeError::Auth(_))));
+        assert!(matches!(send(), Err(ForgeError::Request(_))));
+        assert!(matches!(send(), Err(ForgeError::NotFound(_))));
+        assert!(matches!(send(), Err(ForgeError::Request(_))));
+    }
+}
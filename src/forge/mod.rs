@@ -0,0 +1,366 @@
+//! Backends that know how to talk to a code forge (github, gitlab, gitea, azure, bitbucket,
+//! fixtures, ...).
+//!
+//! `cli::App` only ever talks to a `dyn Forge`, so adding a new host is a matter of adding an
+//! implementation here and teaching `select` how to pick it.
+mod azure;
+mod bitbucket;
+mod dry_run;
+mod fixture;
+mod gh;
+mod gitea;
+mod github;
+mod gitlab;
+mod http;
+mod token;
+
+use std::{error::Error, fmt, sync::Arc};
+
+pub use azure::Azure;
+pub use bitbucket::BitbucketServer;
+use clap::ValueEnum;
+pub use dry_run::DryRun;
+pub use fixture::Fixture;
+pub use gh::GhCli;
+use git2::Repository;
+pub use gitea::Gitea;
+pub use github::GitHub;
+pub use gitlab::GitLab;
+use serde::Serialize;
+
+use crate::{
+    config,
+    prinfo::{PrInfo, StatusCheck},
+    remote::RemoteSpec,
+    shell::{self, ShellError},
+};
+
+/// which backend to talk to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ForgeKind {
+    /// guess from the remote host (or `pr.forge` in git config), for github `gh` if it is
+    /// installed, otherwise the api
+    Auto,
+    /// the `gh` cli
+    Gh,
+    /// the github graphql api
+    Github,
+    /// the gitlab rest api
+    Gitlab,
+    /// the gitea/forgejo rest api
+    Gitea,
+    /// the azure devops rest api
+    Azure,
+    /// the bitbucket server (data center) rest api
+    Bitbucket,
+}
+
+impl ForgeKind {
+    /// the kind of forge a host most likely runs, going by its name
+    fn guess(host: &str) -> ForgeKind {
+        match host {
+            "codeberg.org" => ForgeKind::Gitea,
+            "dev.azure.com" | "ssh.dev.azure.com" => ForgeKind::Azure,
+            host if host.ends_with(".visualstudio.com") => ForgeKind::Azure,
+            // bitbucket.org is bitbucket cloud, which has a different api
+            "bitbucket.org" => ForgeKind::Auto,
+            host if host.contains("bitbucket") => ForgeKind::Bitbucket,
+            host if host.contains("gitlab") => ForgeKind::Gitlab,
+            host if host.contains("gitea") || host.contains("forgejo") => ForgeKind::Gitea,
+            _ => ForgeKind::Auto,
+        }
+    }
+}
+
+/// everything needed to open a new pr
+#[derive(Debug, Clone, Serialize)]
+pub struct NewPr {
+    pub title: String,
+    pub body: String,
+    /// the branch, or `owner:branch` when it lives on a fork
+    pub head: String,
+    pub base: String,
+    pub draft: bool,
+    /// users to request a review from, or teams as `org/team`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reviewers: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assignees: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    /// the milestone's title
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<String>,
+}
+
+impl NewPr {
+    /// the reviewers that are users, and the ones that are teams split into org and team
+    pub fn reviewers(&self) -> (Vec<&str>, Vec<(&str, &str)>) {
+        let mut users = vec![];
+        let mut teams = vec![];
+        for reviewer in &self.reviewers {
+            match reviewer.split_once('/') {
+                Some(team) => teams.push(team),
+                None => users.push(reviewer.as_str()),
+            }
+        }
+        (users, teams)
+    }
+}
+
+/// split a pr head, `branch` or `owner:branch` for a pr from a fork, into the owner and branch
+pub fn split_head(head: &str) -> (Option<&str>, &str) {
+    match head.split_once(':') {
+        Some((owner, branch)) => (Some(owner), branch),
+        None => (None, head),
+    }
+}
+
+/// whether `pr` comes from `owner`'s fork, or from the repo itself when there is no owner, so a
+/// fork that happens to use the same branch name isn't mistaken for ours
+pub fn is_from(pr: &PrInfo, owner: Option<&str>) -> bool {
+    match owner {
+        Some(owner) => pr.headRepositoryOwner.login == owner,
+        None => !pr.isCrossRepository,
+    }
+}
+
+/// the repo and number of the pr a web url points at, e.g. `https://github.com/o/r/pull/12/files`
+/// is 12 on `github.com`, `o`, `r`
+pub fn pr_from_url(url: &str) -> Option<(RemoteSpec, u32)> {
+    let path = url.split(['?', '#']).next()?;
+    let segments = path.split('/').collect::<Vec<&str>>();
+    // the number follows `pull`, `pulls`, `merge_requests`, `pullrequest` or `pull-requests`
+    let at = (0..segments.len().saturating_sub(1))
+        .rev()
+        .find(|&i| segments[i].starts_with("pull") || segments[i] == "merge_requests")?;
+    let number = segments[at + 1].parse().ok()?;
+    // gitlab puts a `-` between the repo and its pages
+    let repo = match &segments[..at] {
+        [repo @ .., "-"] => repo,
+        repo => repo,
+    };
+    Some((RemoteSpec::parse(&repo.join("/"))?, number))
+}
+
+/// `None` for what the backend has no record of, e.g. a pr number that was never used
+pub fn found<T>(fetched: Result<T, ForgeError>) -> Result<Option<T>, ForgeError> {
+    match fetched {
+        Ok(fetched) => Ok(Some(fetched)),
+        Err(ForgeError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(dead_code)]
+pub enum MergeMethod {
+    Merge,
+    Rebase,
+    Squash,
+}
+
+#[derive(Debug)]
+pub enum ForgeError {
+    /// the backend command or request failed, with whatever it told us
+    Command(String),
+    /// the backend answered with something we couldn't make sense of
+    Parse(serde_json::Error),
+    /// an http request to the backend failed
+    Request(String),
+    /// there are no usable credentials for the backend, or it turned them down
+    Auth(String),
+    /// the backend has nothing at this url, or for this command
+    NotFound(String),
+    /// the backend doesn't implement this operation
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for ForgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForgeError::Command(message) => write!(f, "backend command failed: {message}"),
+            ForgeError::Parse(e) => write!(f, "could not parse backend response: {e}"),
+            ForgeError::Request(message) => write!(f, "backend request failed: {message}"),
+            ForgeError::Auth(message) => write!(f, "not authenticated: {message}"),
+            ForgeError::NotFound(what) => write!(f, "backend has nothing at {what}"),
+            ForgeError::Unsupported(what) => write!(f, "backend does not support {what}"),
+        }
+    }
+}
+
+impl Error for ForgeError {}
+
+impl From<ShellError> for ForgeError {
+    fn from(e: ShellError) -> Self {
+        ForgeError::Command(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ForgeError {
+    fn from(e: serde_json::Error) -> Self {
+        ForgeError::Parse(e)
+    }
+}
+
+pub trait Forge: Send + Sync {
+    /// short name of the backend, used in logs
+    fn name(&self) -> &'static str;
+
+    /// fetch the pr whose head is `head`, `None` if there isn't one, see `split_head`
+    fn fetch_pr(&self, head: &str) -> Result<Option<PrInfo>, ForgeError>;
+
+    /// fetch the open pr whose head is `sha`, or failing that one that has `sha` among its
+    /// commits, for when there is no branch to go on
+    fn fetch_pr_by_sha(&self, sha: &str) -> Result<Option<PrInfo>, ForgeError>;
+
+    /// fetch pr `number`, whatever state it is in
+    fn fetch_pr_by_number(&self, number: u32) -> Result<Option<PrInfo>, ForgeError>;
+
+    /// open a new pr and return it
+    fn create_pr(&self, new_pr: &NewPr) -> Result<PrInfo, ForgeError>;
+
+    /// the current checks for a pr
+    // the watch refetches the whole pr, this and the two below aren't wired into the cli yet
+    #[allow(dead_code)]
+    fn list_checks(&self, pr: &PrInfo) -> Result<Vec<StatusCheck>, ForgeError> {
+        Ok(self
+            .fetch_pr(&pr.head())?
+            .map(|pr| pr.statusCheckRollup)
+            .unwrap_or_default())
+    }
+
+    /// leave a comment on a pr
+    #[allow(dead_code)]
+    fn comment(&self, pr: &PrInfo, body: &str) -> Result<(), ForgeError>;
+
+    /// merge a pr
+    #[allow(dead_code)]
+    fn merge(&self, pr: &PrInfo, method: MergeMethod) -> Result<(), ForgeError>;
+
+    /// the branch the repo's prs go into by default
+    fn default_branch(&self) -> Result<String, ForgeError>;
+
+    /// the login the backend is authenticated as, `None` when it can't tell
+    fn viewer(&self) -> Result<Option<String>, ForgeError> {
+        Ok(None)
+    }
+}
+
+/// build the backend for `kind`, `api_url` overrides the default endpoint of api backends
+pub fn select(
+    kind: ForgeKind,
+    repo: &Repository,
+    api_url: Option<&str>,
+) -> Result<Arc<dyn Forge>, ForgeError> {
+    // prs live on the upstream repo, even when branches are pushed to a fork
+    let spec = RemoteSpec::of(repo, &config::upstream_remote(repo)).ok_or(
+        ForgeError::Unsupported("a repo without a forge upstream remote"),
+    )?;
+    let host = &spec.host;
+    let kind = match kind {
+        ForgeKind::Auto => config::forge(repo, host).unwrap_or_else(|| ForgeKind::guess(host)),
+        kind => kind,
+    };
+    let kind = match kind {
+        ForgeKind::Auto if shell::which("gh").is_some() => ForgeKind::Gh,
+        ForgeKind::Auto => ForgeKind::Github,
+        kind => kind,
+    };
+    // api urls live on the web host, which keeps the port of http remotes
+    let web_host = spec.web_host();
+
+    Ok(match kind {
+        ForgeKind::Gh | ForgeKind::Auto => Arc::new(GhCli::new(&web_host, &spec.path())),
+        ForgeKind::Github => {
+            let default_api_url = github::graphql_url(&web_host);
+            Arc::new(GitHub::new(
+                host,
+                api_url.unwrap_or(&default_api_url),
+                &spec.owner,
+                &spec.repo,
+            )?)
+        }
+        ForgeKind::Gitlab => {
+            let default_api_url = format!("https://{web_host}/api/v4");
+            Arc::new(GitLab::new(
+                host,
+                api_url.unwrap_or(&default_api_url),
+                &spec.path(),
+            )?)
+        }
+        ForgeKind::Gitea => {
+            let default_api_url = format!("https://{web_host}/api/v1");
+            Arc::new(Gitea::new(
+                host,
+                api_url.unwrap_or(&default_api_url),
+                &spec.owner,
+                &spec.repo,
+            )?)
+        }
+        ForgeKind::Azure => {
+            let (default_api_url, name) = Azure::api_url_and_repo(host, &spec.path()).ok_or(
+                ForgeError::Unsupported("an azure remote that isn't org/project/repo"),
+            )?;
+            Arc::new(Azure::new(
+                host,
+                api_url.unwrap_or(&default_api_url),
+                &name,
+            )?)
+        }
+        ForgeKind::Bitbucket => {
+            let (project, name) = BitbucketServer::project_and_repo(&spec.path()).ok_or(
+                ForgeError::Unsupported("a bitbucket remote that isn't project/repo"),
+            )?;
+            let default_host_url = format!("https://{web_host}");
+            Arc::new(BitbucketServer::new(
+                host,
+                api_url.unwrap_or(&default_host_url),
+                &project,
+                &name,
+            )?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pr_from_url() {
+        for (url, pr) in [
+            ("https://github.com/o/r/pull/12", Some(("github.com", "o/r", 12))),
+            (
+                "https://github.com/o/r/pull/12/files#diff",
+                Some(("github.com", "o/r", 12)),
+            ),
+            (
+                "https://gitlab.com/g/sub/r/-/merge_requests/7/diffs",
+                Some(("gitlab.com", "g/sub/r", 7)),
+            ),
+            (
+                "https://dev.azure.com/o/p/_git/r/pullrequest/42?_a=files",
+                Some(("dev.azure.com", "o/p/_git/r", 42)),
+            ),
+            (
+                "https://bitbucket.example.com/projects/P/repos/r/pull-requests/101/overview",
+                Some(("bitbucket.example.com", "projects/P/repos/r", 101)),
+            ),
+            (
+                "https://codeberg.org/o/r/pulls/3",
+                Some(("codeberg.org", "o/r", 3)),
+            ),
+            ("https://github.com/o/r/issues/12", None),
+            ("https://github.com/pull/12", None),
+        ] {
+            let found = pr_from_url(url);
+            let found = found
+                .as_ref()
+                .map(|(spec, number)| (spec.host.as_str(), spec.path(), *number));
+            let pr = pr.map(|(host, path, number)| (host, path.to_string(), number));
+            assert_eq!(found, pr, "{url}");
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Finding credentials for a forge host.
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct GhHost {
+    oauth_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TeaConfig {
+    logins: Vec<TeaLogin>,
+}
+
+#[derive(Deserialize)]
+struct TeaLogin {
+    url: String,
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GlabConfig {
+    hosts: HashMap<String, GlabHost>,
+}
+
+#[derive(Deserialize)]
+struct GlabHost {
+    token: Option<String>,
+}
+
+/// a token for a github host, from the same variables gh reads (`GH_TOKEN`/`GITHUB_TOKEN` for
+/// github.com, `GH_ENTERPRISE_TOKEN`/`GITHUB_ENTERPRISE_TOKEN` for anything else) or gh's
+/// `hosts.yml`
+pub fn github_token(host: &str) -> Option<String> {
+    let vars = match host {
+        "github.com" => ["GH_TOKEN", "GITHUB_TOKEN"],
+        _ => ["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"],
+    };
+    env_token(&vars).or_else(|| gh_hosts_token(host))
+}
+
+/// a token for a gitlab host, from `GITLAB_TOKEN` or glab's `config.yml`
+pub fn gitlab_token(host: &str) -> Option<String> {
+    env_token(&["GITLAB_TOKEN", "GL_TOKEN"]).or_else(|| glab_hosts_token(host))
+}
+
+/// a token for a gitea or forgejo host, from `GITEA_TOKEN`/`FORGEJO_TOKEN` or tea's `config.yml`
+pub fn gitea_token(host: &str) -> Option<String> {
+    env_token(&["GITEA_TOKEN", "FORGEJO_TOKEN"]).or_else(|| tea_login_token(host))
+}
+
+/// a personal access token for azure devops, azure's own cli reads the same variable
+pub fn azure_token() -> Option<String> {
+    env_token(&["AZURE_DEVOPS_EXT_PAT", "AZURE_DEVOPS_TOKEN"])
+}
+
+/// an http access token for bitbucket server
+pub fn bitbucket_token() -> Option<String> {
+    env_token(&["BITBUCKET_TOKEN"])
+}
+
+/// the first of `vars` that is set to something
+pub fn env_token(vars: &[&str]) -> Option<String> {
+    vars.iter()
+        .find_map(|var| env::var(var).ok().filter(|token| !token.is_empty()))
+}
+
+/// `$XDG_CONFIG_HOME/<name>`, falling back to `~/.config/<name>`
+fn config_dir(name: &str) -> Option<PathBuf> {
+    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        return Some(Path::new(&dir).join(name))
+    }
+    env::var("HOME")
+        .ok()
+        .map(|home| Path::new(&home).join(".config").join(name))
+}
+
+fn gh_config_dir() -> Option<PathBuf> {
+    match env::var("GH_CONFIG_DIR") {
+        Ok(dir) => Some(PathBuf::from(dir)),
+        Err(_) => config_dir("gh"),
+    }
+}
+
+fn gh_hosts_token(host: &str) -> Option<String> {
+    let hosts = fs::read_to_string(gh_config_dir()?.join("hosts.yml")).ok()?;
+    let mut hosts = serde_yaml::from_str::<HashMap<String, GhHost>>(&hosts).ok()?;
+    hosts.remove(host)?.oauth_token
+}
+
+fn glab_hosts_token(host: &str) -> Option<String> {
+    let config = fs::read_to_string(config_dir("glab-cli")?.join("config.yml")).ok()?;
+    let mut config = serde_yaml::from_str::<GlabConfig>(&config).ok()?;
+    config.hosts.remove(host)?.token
+}
+
+fn tea_login_token(host: &str) -> Option<String> {
+    let config = fs::read_to_string(config_dir("tea")?.join("config.yml")).ok()?;
+    let config = serde_yaml::from_str::<TeaConfig>(&config).ok()?;
+    config
+        .logins
+        .into_iter()
+        .find(|login| login.url.split("://").last() == Some(host))?
+        .token
+}
@@ -1,43 +1,301 @@
-use git2::{Branch, Repository};
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+};
 
-pub fn current_repo() -> Repository {
-    return match Repository::init(".") {
-        Ok(repo) => repo,
-        Err(e) => panic!("failed to init: {}", e),
+use git2::{Branch, Commit, ErrorCode, Oid, Repository, Sort};
+
+use crate::{config, forge::Forge, prinfo::PrInfo, remote::RemoteSpec};
+
+#[derive(Debug)]
+pub enum RepoError {
+    /// there is no repository at or above this path
+    NotFound(PathBuf),
+    /// there is a repository but git2 couldn't open it
+    Open(git2::Error),
+}
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::NotFound(path) => write!(
+                f,
+                "{} is not in a git repository, run this from inside one or pass --repo <path>",
+                path.display()
+            ),
+            RepoError::Open(e) => write!(f, "could not open the git repository: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RepoError {}
+
+/// the repository at or above `path`, or when there is no path the one git itself would use:
+/// `GIT_DIR` and `GIT_WORK_TREE` if they are set, otherwise the one the current directory is in,
+/// linked worktrees included
+pub fn current_repo(path: Option<&Path>) -> Result<Repository, RepoError> {
+    let opened = match path {
+        Some(path) => Repository::discover(path),
+        None => Repository::open_from_env().and_then(|repo| {
+            // libgit2 reads GIT_DIR but leaves GIT_WORK_TREE to us
+            if let Some(work_tree) = env::var_os("GIT_WORK_TREE") {
+                repo.set_workdir(Path::new(&work_tree), false)?;
+            }
+            Ok(repo)
+        }),
     };
+    opened.map_err(|e| match e.code() {
+        ErrorCode::NotFound => RepoError::NotFound(match path {
+            Some(path) => path.to_path_buf(),
+            None => env::current_dir().unwrap_or_default(),
+        }),
+        _ => RepoError::Open(e),
+    })
 }
 
-pub fn remote_gh_name(repo: &Repository) -> String {
-    let found_remote = repo.find_remote("origin").unwrap();
-    let url = found_remote.url().unwrap();
-    let remote_name = &url[url.find("/").unwrap() + 1..url.find(".git").unwrap()];
-    return remote_name.to_string();
+/// what the forge calls `branch` once pushed, `owner:branch` when the push remote is a fork of the
+/// upstream remote
+pub fn pr_head(repo: &Repository, branch: &str) -> String {
+    let push = RemoteSpec::of(repo, &config::push_remote(repo));
+    let upstream = RemoteSpec::of(repo, &config::upstream_remote(repo));
+    match (push, upstream) {
+        (Some(push), Some(upstream)) if push.owner != upstream.owner => {
+            format!("{}:{branch}", push.owner)
+        }
+        _ => branch.to_string(),
+    }
 }
 
-pub fn current_branch(repo: &Repository) -> Option<Branch> {
+pub fn current_branch(repo: &Repository) -> Option<Branch<'_>> {
     let head = repo.head().ok()?;
     let name = head.shorthand()?;
-    return repo.find_branch(name, git2::BranchType::Local).ok();
+    repo.find_branch(name, git2::BranchType::Local).ok()
 }
 
+/// the checked out branch, or when HEAD is detached by a rebase the branch being rebased
 pub fn current_branch_name(repo: &Repository) -> Option<String> {
-    return current_branch(repo)?
-        .name()
-        .map_or(None, |s| Some(String::from(s?)));
+    current_branch(repo)
+        .and_then(|branch| branch.name().ok().flatten().map(String::from))
+        .or_else(|| rebase_head_name(repo))
 }
 
-pub fn get_main_branch(repo: &Repository) -> Result<Branch, &'static str> {
-    for branch in ["main", "master"] {
-        match repo.find_branch(branch, git2::BranchType::Local) {
-            Ok(b) => return Ok(b),
-            Err(_) => continue,
+/// the branch an in progress rebase started from, `rebase-merge` is interactive and merge based
+/// rebases, `rebase-apply` is `git am` style ones
+pub fn rebase_head_name(repo: &Repository) -> Option<String> {
+    ["rebase-merge", "rebase-apply"].iter().find_map(|dir| {
+        let head_name = fs::read_to_string(repo.path().join(dir).join("head-name")).ok()?;
+        head_name
+            .trim()
+            .strip_prefix("refs/heads/")
+            .map(String::from)
+    })
+}
+
+/// where a local branch stands against the commit its remote copy is at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Divergence {
+    UpToDate,
+    /// has commits the remote doesn't, a plain push is enough
+    Ahead(usize),
+    /// the remote has commits the branch doesn't
+    Behind(usize),
+    /// both have commits the other doesn't, e.g. after a rebase, only a forced push will do
+    Diverged {
+        ahead: usize,
+        behind: usize,
+    },
+    /// the remote commit isn't in the local repo, it needs fetching before it can be compared
+    Unknown,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::UpToDate => write!(f, "up to date with"),
+            Divergence::Ahead(ahead) => write!(f, "{ahead} commits ahead of"),
+            Divergence::Behind(behind) => write!(f, "{behind} commits behind"),
+            Divergence::Diverged { ahead, behind } => {
+                write!(f, "diverged ({ahead} ahead, {behind} behind) from")
+            }
+            Divergence::Unknown => write!(f, "not comparable, fetch first, with"),
         }
     }
-    Err("no branch found")
 }
 
-pub fn get_merge_base(repo: &Repository, main_branch: &Branch) -> git2::Oid {
-    let head_oid = repo.head().unwrap().target().unwrap();
-    let main_oid = main_branch.get().target().unwrap();
-    repo.merge_base(head_oid, main_oid).unwrap()
+/// where `local` stands against `remote`
+pub fn divergence(repo: &Repository, local: Oid, remote: Oid) -> Divergence {
+    match repo.graph_ahead_behind(local, remote) {
+        Ok((0, 0)) => Divergence::UpToDate,
+        Ok((ahead, 0)) => Divergence::Ahead(ahead),
+        Ok((0, behind)) => Divergence::Behind(behind),
+        Ok((ahead, behind)) => Divergence::Diverged { ahead, behind },
+        Err(_) => Divergence::Unknown,
+    }
+}
+
+/// where `remote` had `branch` when it was last fetched from or pushed to
+pub fn remote_branch_oid(repo: &Repository, remote: &str, branch: &str) -> Option<Oid> {
+    repo.refname_to_id(&format!("refs/remotes/{remote}/{branch}"))
+        .ok()
+}
+
+/// the commit HEAD points at, branch or not
+pub fn head_sha(repo: &Repository) -> Option<String> {
+    Some(repo.head().ok()?.target()?.to_string())
+}
+
+/// the branch prs are based on, from the first of
+///
+/// - `pr.base` in git config
+/// - the base of the branch's existing pr
+/// - `refs/remotes/<remote>/HEAD`, which clone sets up
+/// - the forge's default branch
+/// - `main` or `master`, if either exists
+pub fn default_branch(
+    repo: &Repository,
+    remote: &str,
+    pr: Option<&PrInfo>,
+    forge: &dyn Forge,
+) -> Option<String> {
+    config::base_branch(repo)
+        .or_else(|| pr.map(|pr| pr.baseRefName.clone()))
+        .filter(|base| !base.is_empty())
+        .or_else(|| remote_head(repo, remote))
+        .or_else(|| forge.default_branch().ok())
+        .or_else(|| get_main_branch(repo, remote))
+}
+
+/// the branch `refs/remotes/<remote>/HEAD` points at
+pub fn remote_head(repo: &Repository, remote: &str) -> Option<String> {
+    let head = repo
+        .find_reference(&format!("refs/remotes/{remote}/HEAD"))
+        .ok()?;
+    let target = head.symbolic_target()?;
+    let prefix = format!("refs/remotes/{remote}/");
+    target.strip_prefix(&prefix).map(String::from)
+}
+
+/// `main` or `master`, whichever exists locally or on `remote`
+pub fn get_main_branch(repo: &Repository, remote: &str) -> Option<String> {
+    ["main", "master"]
+        .into_iter()
+        .find(|branch| base_oid(repo, remote, branch).is_some())
+        .map(String::from)
+}
+
+/// where `base` is at, `remote`'s copy is preferred since that is what the pr is against
+fn base_oid(repo: &Repository, remote: &str, base: &str) -> Option<Oid> {
+    [
+        format!("refs/remotes/{remote}/{base}"),
+        format!("refs/heads/{base}"),
+    ]
+    .iter()
+    .find_map(|name| repo.refname_to_id(name).ok())
+}
+
+/// the commits in `base..HEAD`, oldest first
+pub fn commits_since(repo: &Repository, base: Oid) -> Result<Vec<Commit<'_>>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push_head()?;
+    walk.hide(base)?;
+    walk.map(|oid| repo.find_commit(oid?)).collect()
+}
+
+/// the paths changed between `base` and HEAD
+pub fn changed_files(repo: &Repository, base: Oid) -> Result<Vec<String>, git2::Error> {
+    let base = repo.find_commit(base)?.tree()?;
+    let head = repo.head()?.peel_to_tree()?;
+    let diff = repo.diff_tree_to_tree(Some(&base), Some(&head), None)?;
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect())
+}
+
+pub fn get_merge_base(repo: &Repository, remote: &str, base: &str) -> Option<Oid> {
+    let head_oid = repo.head().ok()?.target()?;
+    let base_oid = base_oid(repo, remote, base)?;
+    repo.merge_base(head_oid, base_oid).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::Signature;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::forge::Fixture;
+
+    fn commit(repo: &Repository, parent: Option<Oid>, message: &str) -> Oid {
+        let signature = Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let parents = parent
+            .map(|parent| repo.find_commit(parent).unwrap())
+            .into_iter()
+            .collect::<Vec<Commit>>();
+        let parents = parents.iter().collect::<Vec<&Commit>>();
+        repo.commit(None, &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_divergence() {
+        let dir = env::temp_dir().join(format!("git-pr-divergence-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let base = commit(&repo, None, "base");
+        let local = commit(&repo, Some(base), "local");
+        let local_again = commit(&repo, Some(local), "local again");
+        let remote = commit(&repo, Some(base), "remote");
+
+        assert_eq!(divergence(&repo, local, local), Divergence::UpToDate);
+        assert_eq!(divergence(&repo, local_again, base), Divergence::Ahead(2));
+        assert_eq!(divergence(&repo, base, remote), Divergence::Behind(1));
+        assert_eq!(
+            divergence(&repo, local_again, remote),
+            Divergence::Diverged {
+                ahead: 2,
+                behind: 1
+            }
+        );
+        let missing = Oid::from_str("0d1f3c9a8b7e6d5c4b3a29180f7e6d5c4b3a2918").unwrap();
+        assert_eq!(divergence(&repo, local, missing), Divergence::Unknown);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_branch() {
+        let dir = env::temp_dir().join(format!("git-pr-default-branch-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let base = commit(&repo, None, "base");
+        repo.reference("refs/heads/main", base, false, "").unwrap();
+        // an empty fixture has no default branch to give
+        let fixture = dir.join("prs.json");
+        fs::write(&fixture, "[]").unwrap();
+        let forge = Fixture::load(fixture.to_str().unwrap()).unwrap();
+        let pr = |base: &str| PrInfo {
+            baseRefName: base.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(default_branch(&repo, "origin", None, &forge).as_deref(), Some("main"));
+        let develop = pr("develop");
+        assert_eq!(
+            default_branch(&repo, "origin", Some(&develop), &forge).as_deref(),
+            Some("develop")
+        );
+        assert_eq!(
+            default_branch(&repo, "origin", Some(&pr("")), &forge).as_deref(),
+            Some("main")
+        );
+        repo.config().unwrap().set_str("pr.base", "release").unwrap();
+        assert_eq!(
+            default_branch(&repo, "origin", Some(&develop), &forge).as_deref(),
+            Some("release")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
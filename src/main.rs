@@ -1,7 +1,17 @@
+
 pub mod args;
+mod blame;
 pub mod cli;
+mod codeowners;
+mod config;
+mod error;
+mod exit;
+mod forge;
 mod git_commands;
+mod plan;
 mod prinfo;
+mod push;
+mod remote;
 mod shell;
 
 use simple_logger::SimpleLogger;
@@ -9,14 +19,17 @@ use simple_logger::SimpleLogger;
 #[tokio::main]
 
 async fn main() {
-    let logger = SimpleLogger::new().init().unwrap();
+    SimpleLogger::new()
+        .with_module_level("ureq", log::LevelFilter::Info)
+        .init()
+        .unwrap();
     log::set_max_level(log::LevelFilter::Debug);
 
     std::process::exit(match cli::main().await {
-        Ok(_) => 0,
+        Ok(code) => code,
         Err(err) => {
-            eprintln!("error: {:?}", err);
-            1
+            eprintln!("error: {err}");
+            exit::of_error(&err)
         }
     });
 }
@@ -0,0 +1,41 @@
+//! The side effects `--dry-run` stands in for, printed as a yaml plan instead of being carried out.
+use log::warn;
+use serde::Serialize;
+
+use crate::forge::{MergeMethod, NewPr};
+
+/// something that would have changed a remote or a forge
+#[derive(Debug, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Push {
+        remote: String,
+        branch: String,
+        /// the commit the remote branch must still be at for a forced push
+        #[serde(skip_serializing_if = "Option::is_none")]
+        force_with_lease: Option<String>,
+    },
+    CreatePr {
+        forge: &'static str,
+        #[serde(flatten)]
+        pr: NewPr,
+    },
+    Comment {
+        forge: &'static str,
+        number: u32,
+        body: String,
+    },
+    Merge {
+        forge: &'static str,
+        number: u32,
+        method: MergeMethod,
+    },
+}
+
+/// print `action` as the next item of the plan
+pub fn print(action: &Action) {
+    match serde_yaml::to_string(&[action]) {
+        Ok(yaml) => print!("{yaml}"),
+        Err(e) => warn!("could not print {action:?}: {e}"),
+    }
+}
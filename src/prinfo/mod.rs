@@ -1,4 +1,11 @@
 mod models;
+#[allow(clippy::module_inception)]
 mod prinfo;
+mod template;
 
-pub use models::{File, PrInfo};
+pub use models::{
+    CheckConclusionState, CheckOutcome, CheckStatusState, Commit, File, Label, Node, PrInfo, Repo,
+    Review, StatusCheck, StatusContextState, User,
+};
+pub use prinfo::{pr_base, push_branch, CreateOptions};
+pub use template::TemplateError;
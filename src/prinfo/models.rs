@@ -1,8 +1,10 @@
-use std::{fmt, result, time::SystemTime};
+// the models mirror gh's camelCase json field names
+#![allow(non_snake_case)]
+
+use std::{fmt, time::SystemTime};
 
 use colored::{ColoredString, Colorize};
-use log::debug;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Serialize};
 use struct_field_names_as_array::FieldNamesAsArray;
 
 /// fetch the pr info for the given branch
@@ -42,7 +44,7 @@ pub enum StatusContextState {
     Pending,  //Status is pending.
     Success,  //Status is successful.
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct User {
     pub login: String,
     pub email: Option<String>,
@@ -50,7 +52,7 @@ pub struct User {
     pub name: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Commit {
     pub authoredDate: String,
     pub authors: Vec<User>,
@@ -60,34 +62,64 @@ pub struct Commit {
     pub oid: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct File {
     pub path: String,
     pub additions: usize,
     pub deletions: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Repo {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Node {
     pub oid: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Review {
-    id: String,
-    author: User,
-    authorAssociation: String,
-    body: String,
-    submittedAt: String,
-    includesCreatedEdit: bool,
-    reactionGroups: Vec<String>,
-    state: String,
+    pub id: String,
+    pub author: User,
+    pub authorAssociation: String,
+    pub body: String,
+    pub submittedAt: String,
+    pub includesCreatedEdit: bool,
+    pub reactionGroups: Vec<String>,
+    pub state: String,
+}
+
+/// the name of a user, team or milestone, which gh hands out as an object, e.g. `{"login": ..}`
+/// for a user, `{"slug": ..}` for a team and `{"title": ..}` for a milestone
+fn name_of(value: serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(name) => Some(name),
+        serde_json::Value::Object(object) => ["login", "slug", "name", "title"]
+            .iter()
+            .find_map(|key| object.get(*key)?.as_str().map(String::from)),
+        _ => None,
+    }
+}
+
+fn names<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<Vec<serde_json::Value>>::deserialize(deserializer)?
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(name_of)
+        .collect())
+}
+
+fn name<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<serde_json::Value>::deserialize(deserializer)?.and_then(name_of))
 }
 
 fn error_as_none<'de, D>(deserializer: D) -> Result<Option<CheckConclusionState>, D::Error>
@@ -96,7 +128,7 @@ where
 {
     match CheckConclusionState::deserialize(deserializer) {
         Ok(result) => Ok(Some(result)),
-        Err(e) => Ok(None),
+        Err(_) => Ok(None),
     }
 }
 
@@ -121,7 +153,7 @@ pub enum StatusCheck {
     },
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, FieldNamesAsArray)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, FieldNamesAsArray)]
 pub struct Label {
     pub id: String,
     pub name: String,
@@ -143,12 +175,19 @@ pub struct Comment {
     viewerDidAuthor: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, FieldNamesAsArray)]
+/// fields that older github enterprise servers don't have are left at their defaults
+#[derive(Debug, Default, Serialize, Deserialize, Clone, FieldNamesAsArray)]
+#[serde(default)]
 pub struct PrInfo {
     #[serde(skip)]
     #[field_names_as_array(skip)]
     pub __createdAt: Option<SystemTime>,
+    /// why the last refresh failed, while what is shown is from before it
+    #[serde(skip)]
+    #[field_names_as_array(skip)]
+    pub __refreshError: Option<String>,
     pub additions: u32,
+    #[serde(deserialize_with = "names")]
     pub assignees: Vec<String>,
     pub author: User,
     pub baseRefName: String,
@@ -176,12 +215,14 @@ pub struct PrInfo {
     pub mergeable: String,
     pub mergedAt: Option<String>,
     pub mergedBy: Option<User>,
+    #[serde(deserialize_with = "name")]
     pub milestone: Option<String>,
     pub number: u32,
     pub potentialMergeCommit: Option<Node>,
     pub projectCards: Vec<String>,
     pub reactionGroups: Vec<String>,
     pub reviewDecision: String,
+    #[serde(deserialize_with = "names")]
     pub reviewRequests: Vec<String>,
     pub reviews: Vec<Review>,
     pub state: String,
@@ -191,9 +232,9 @@ pub struct PrInfo {
     pub url: String,
 }
 
-impl Into<String> for File {
-    fn into(self) -> String {
-        self.to_string()
+impl From<File> for String {
+    fn from(file: File) -> Self {
+        file.to_string()
     }
 }
 
@@ -209,40 +250,49 @@ impl fmt::Display for File {
     }
 }
 
-impl Into<String> for StatusCheck {
-    fn into(self) -> String {
-        self.to_string()
+impl From<StatusCheck> for String {
+    fn from(status_check: StatusCheck) -> Self {
+        status_check.to_string()
     }
 }
 
-impl CheckStatusState {
-    pub fn is_complete(&self) -> bool {
+/// how a check turned out, or all of a pr's checks together, worst last
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckOutcome {
+    Passed,
+    Pending,
+    /// cancelled or timed out
+    Cancelled,
+    Failed,
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CheckStatusState::Completed => true,
-            _ => false,
+            CheckOutcome::Passed => write!(f, "all checks passed"),
+            CheckOutcome::Pending => write!(f, "the checks haven't all finished"),
+            CheckOutcome::Cancelled => write!(f, "some checks were cancelled or timed out"),
+            CheckOutcome::Failed => write!(f, "some checks failed"),
         }
     }
 }
 
-impl StatusContextState {
+impl CheckStatusState {
     pub fn is_complete(&self) -> bool {
-        match self {
-            StatusContextState::Success => true,
-            StatusContextState::Failure => true,
-            StatusContextState::Error => true,
-            _ => false,
-        }
+        matches!(self, CheckStatusState::Completed)
     }
 }
 
-impl StatusCheck {
-    fn type_name(&self) -> &'static str {
-        match self {
-            StatusCheck::CheckRun { .. } => todo!(),
-            StatusCheck::StatusContext { .. } => todo!(),
-        }
+impl StatusContextState {
+    pub fn is_complete(&self) -> bool {
+        matches!(
+            self,
+            StatusContextState::Success | StatusContextState::Failure | StatusContextState::Error
+        )
     }
+}
 
+impl StatusCheck {
     pub fn name(&self) -> String {
         match self {
             StatusCheck::CheckRun { name, .. } => name.clone(),
@@ -257,6 +307,37 @@ impl StatusCheck {
         }
     }
 
+    pub fn outcome(&self) -> CheckOutcome {
+        if !self.is_complete() {
+            return CheckOutcome::Pending
+        }
+        match self {
+            StatusCheck::CheckRun { conclusion, .. } => match conclusion {
+                Some(
+                    CheckConclusionState::ActionRequired
+                    | CheckConclusionState::Failure
+                    | CheckConclusionState::Stale
+                    | CheckConclusionState::StartupFailure,
+                ) => CheckOutcome::Failed,
+                Some(CheckConclusionState::Cancelled | CheckConclusionState::TimedOut) => {
+                    CheckOutcome::Cancelled
+                }
+                // a conclusion we don't know of is taken as neutral
+                Some(
+                    CheckConclusionState::Neutral
+                    | CheckConclusionState::Skipped
+                    | CheckConclusionState::Success,
+                )
+                | None => CheckOutcome::Passed,
+            },
+            StatusCheck::StatusContext { state, .. } => match state {
+                StatusContextState::Error | StatusContextState::Failure => CheckOutcome::Failed,
+                StatusContextState::Success => CheckOutcome::Passed,
+                StatusContextState::Expected | StatusContextState::Pending => CheckOutcome::Pending,
+            },
+        }
+    }
+
     pub fn short_status_string(&self) -> String {
         self.short_status_str().to_string()
     }
@@ -278,14 +359,13 @@ impl StatusCheck {
                     CheckConclusionState::StartupFailure => "Fail",
                     CheckConclusionState::Success => " OK ",
                     CheckConclusionState::TimedOut => "Fail",
-                    _ => panic!("unexpected status context state: {:?}", conclusion),
                 },
             },
             StatusCheck::StatusContext { state, .. } => match state {
                 StatusContextState::Error => "Fail",
                 StatusContextState::Failure => "Fail",
                 StatusContextState::Success => "Pass",
-                _ => panic!("unexpected status context state: {:?}", state),
+                StatusContextState::Expected | StatusContextState::Pending => " .. ",
             },
         }
     }
@@ -1,16 +1,56 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
-use git2::Repository;
+use git2::{Commit, Oid, Repository};
+use indicatif::ProgressBar;
 use indoc::formatdoc;
-use log::{debug, info};
-use serde_json::from_str;
+use log::{info, warn};
 
 use crate::{
-    git_commands::{current_branch_name, get_main_branch, get_merge_base},
-    prinfo::models::PrInfo,
-    shell,
+    blame,
+    codeowners::{self, CodeOwners},
+    config,
+    error::Error,
+    forge::{Forge, ForgeError, NewPr},
+    git_commands::{
+        changed_files, commits_since, current_branch_name, default_branch, get_merge_base, pr_head,
+        remote_branch_oid,
+    },
+    plan::{self, Action},
+    prinfo::{
+        models::{CheckOutcome, PrInfo, Review, StatusCheck},
+        template,
+    },
+    push::push,
 };
 
+/// how long fetched pr info is shown before it is fetched again
+const REFRESH_EVERY: Duration = Duration::from_secs(15);
+
+/// how many of the people blame suggests are shown while opening a pr
+const SUGGESTED_REVIEWERS: usize = 3;
+
+/// how to open a new pr
+#[derive(Debug, Default, Clone)]
+pub struct CreateOptions {
+    pub draft: bool,
+    /// the pr template to use, by name, when the repo has several
+    pub template: Option<String>,
+    /// review the title and body in an editor before the pr is opened
+    pub edit: bool,
+    /// print the push instead of doing it, the forge is expected to be a `DryRun` too
+    pub dry_run: bool,
+    /// overwrite the remote branch, e.g. after a rebase, as long as it is where we last saw it
+    pub force_with_lease: bool,
+    /// users, or `org/team` teams, to request a review from
+    pub reviewers: Vec<String>,
+    /// also request a review from the CODEOWNERS of the changed files
+    pub request_owners: bool,
+    pub assignees: Vec<String>,
+    pub labels: Vec<String>,
+    /// the milestone's title
+    pub milestone: Option<String>,
+}
+
 pub fn map_to_string<S: Into<String>>(vec: Vec<S>) -> String where {
     vec.into_iter()
         .map(|s| s.into())
@@ -18,9 +58,130 @@ pub fn map_to_string<S: Into<String>>(vec: Vec<S>) -> String where {
         .join("\n")
 }
 
+/// `branch` as a title, e.g. `feature/retry-uploads` is `Retry uploads`
+fn branch_title(branch: &str) -> String {
+    let name = branch
+        .rsplit('/')
+        .next()
+        .unwrap_or(branch)
+        .replace(['-', '_'], " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// a pr title and body for `commits`, one commit speaks for itself, several get a title picked by
+/// `pr.title` and a list of their subjects
+pub fn title_and_body(repo: &Repository, branch: &str, commits: &[Commit]) -> (String, String) {
+    let subject = |commit: &Commit| commit.summary().unwrap_or_default().to_string();
+    match commits {
+        [] => (branch_title(branch), String::new()),
+        [commit] => (
+            subject(commit),
+            commit.body().unwrap_or_default().trim().to_string(),
+        ),
+        [first, .., last] => {
+            let title = match config::multi_commit_title(repo).as_str() {
+                "first" => subject(first),
+                "last" => subject(last),
+                _ => branch_title(branch),
+            };
+            let body = commits
+                .iter()
+                .map(|commit| format!("- {}", subject(commit)))
+                .collect::<Vec<String>>()
+                .join("\n");
+            (title, body)
+        }
+    }
+}
+
+/// push `branch` to the push remote, forced if there is a `lease` it must still be at, or with
+/// `dry_run` print the push instead
+pub fn push_branch(
+    repo: &Repository,
+    branch: &str,
+    lease: Option<Oid>,
+    dry_run: bool,
+    progress: &ProgressBar,
+) -> Result<(), Error> {
+    let push_remote = config::push_remote(repo);
+    if dry_run {
+        plan::print(&Action::Push {
+            remote: push_remote,
+            branch: branch.to_string(),
+            force_with_lease: lease.map(|oid| oid.to_string()),
+        });
+        return Ok(())
+    }
+    match lease {
+        Some(lease) => info!("pushing {branch} to {push_remote}, forced if it is still at {lease}"),
+        None => info!("pushing {branch} to {push_remote}"),
+    }
+    push(repo, &push_remote, branch, lease, progress)
+        .map_err(|e| Error::Push(format!("{branch} to {push_remote}"), e))
+}
+
+/// the branch `pr` is against, or a new pr for the current branch would be opened against, and
+/// where HEAD left it
+pub fn pr_base(
+    repo: &Repository,
+    pr: Option<&PrInfo>,
+    forge: &dyn Forge,
+) -> Result<(String, Oid), Error> {
+    let upstream_remote = config::upstream_remote(repo);
+    let base = default_branch(repo, &upstream_remote, pr, forge).ok_or_else(|| {
+        Error::NotFound(
+            "could not find the branch to open the pr against, set it with `git config pr.base \
+             <branch>`"
+                .to_string(),
+        )
+    })?;
+    let merge_base = get_merge_base(repo, &upstream_remote, &base).ok_or_else(|| {
+        Error::NotFound(format!(
+            "HEAD has nothing in common with {base}, fetch {upstream_remote} and check it is the \
+             right base"
+        ))
+    })?;
+    Ok((base, merge_base))
+}
+
+/// the logins of whoever opens the pr, and of whoever wrote `commits` as far as their emails
+/// give it away
+fn authors(forge: &dyn Forge, commits: &[Commit]) -> Vec<String> {
+    let mut authors = commits
+        .iter()
+        .filter_map(|commit| Some(blame::login_from_email(commit.author().email()?)?.to_string()))
+        .collect::<Vec<String>>();
+    match forge.viewer() {
+        Ok(Some(viewer)) => authors.push(viewer),
+        Ok(None) => {}
+        Err(e) => warn!("could not tell who is opening the pr, they may be asked to review: {e}"),
+    }
+    authors
+}
+
+/// the people to ask for a review of `base..HEAD` by blame, described one per line
+fn suggested_reviewers(repo: &Repository, base: Oid) -> Vec<String> {
+    match blame::suggest(repo, base) {
+        Ok((candidates, total)) => candidates
+            .iter()
+            .take(SUGGESTED_REVIEWERS)
+            .map(|candidate| candidate.describe(total))
+            .collect(),
+        Err(e) => {
+            warn!("could not suggest reviewers: {e}");
+            vec![]
+        }
+    }
+}
+
 impl PrInfo {
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        return formatdoc!(
+        formatdoc!(
             "
                 ====> #{number_hash} — {title}
                 {body}
@@ -51,96 +212,394 @@ impl PrInfo {
         )
     }
 
+    /// the logins whose latest review approved the pr, from `latestReviews` or, when the backend
+    /// left those out, from the reviews in the order they were given
+    pub fn approvers(&self) -> Vec<String> {
+        let reviews = match self.latestReviews.is_empty() {
+            true => &self.reviews,
+            false => &self.latestReviews,
+        };
+        let mut latest: Vec<&Review> = vec![];
+        // comments don't change where a reviewer stands
+        for review in reviews
+            .iter()
+            .filter(|review| review.state != "COMMENTED" && review.state != "PENDING")
+        {
+            latest.retain(|earlier| earlier.author.login != review.author.login);
+            latest.push(review);
+        }
+        latest
+            .into_iter()
+            .filter(|review| review.state == "APPROVED")
+            .map(|review| review.author.login.clone())
+            .collect()
+    }
+
+    /// the head commit, which a pr that is yet to be opened doesn't know
     pub fn sha(&self) -> String {
-        return self.commits.last().cloned().unwrap().oid
+        self.commits
+            .last()
+            .map(|commit| commit.oid.clone())
+            .unwrap_or_else(|| self.headRefOid.clone())
     }
 
+    /// the head as `fetch_pr` takes it, `owner:branch` for a pr from a fork
+    pub fn head(&self) -> String {
+        match self.isCrossRepository && !self.headRepositoryOwner.login.is_empty() {
+            true => format!("{}:{}", self.headRepositoryOwner.login, self.headRefName),
+            false => self.headRefName.clone(),
+        }
+    }
+
+    /// whether every check has finished, a pr whose checks haven't shown up yet isn't done
     pub fn is_complete(&self) -> bool {
-        self.statusCheckRollup.iter().all(|s| s.is_complete())
-    }
-
-    /// fetch the pr info from github via their api
-    pub fn get<S: Into<String>>(branch: S) -> Option<PrInfo> {
-        // todo: migrate to the gh structured format
-        let branch: String = branch.into();
-        let format_str = PrInfo::FIELD_NAMES_AS_ARRAY.join(",");
-        let cmd = format!("gh pr list --json {format_str} -H {branch}");
-
-        let stdout = if branch.starts_with("gh-mock-") {
-            Some(
-                match branch.as_str() {
-                    "gh-mock-fix-main" => include_str!("mock_data/d1.json"),
-                    "gh-mock-simple" => include_str!("mock_data/d2.json"),
-                    "gh-mock-building" => include_str!("mock_data/d4.json"),
-                    "gh-mock-done" => include_str!("mock_data/d2.json"),
-                    _ => panic!("no mock for {}", branch),
+        !self.statusCheckRollup.is_empty() && self.statusCheckRollup.iter().all(|s| s.is_complete())
+    }
+
+    /// the checks that are done and failed, of the `required` ones when there are any
+    pub fn failed_checks(&self, required: &[String]) -> Vec<&StatusCheck> {
+        self.statusCheckRollup
+            .iter()
+            .filter(|check| required.is_empty() || required.contains(&check.name()))
+            .filter(|check| check.outcome() == CheckOutcome::Failed)
+            .collect()
+    }
+
+    /// the checks that are yet to finish
+    pub fn pending_checks(&self) -> Vec<&StatusCheck> {
+        self.statusCheckRollup
+            .iter()
+            .filter(|check| !check.is_complete())
+            .collect()
+    }
+
+    /// how the checks turned out together, the worst of them, pending when there are none yet
+    pub fn outcome(&self) -> CheckOutcome {
+        self.statusCheckRollup
+            .iter()
+            .map(StatusCheck::outcome)
+            .max()
+            .unwrap_or(CheckOutcome::Pending)
+    }
+
+    /// fetch the pr info for a head, `branch` or `owner:branch`, from the forge
+    pub fn get<S: Into<String>>(forge: &dyn Forge, head: S) -> Result<Option<PrInfo>, ForgeError> {
+        let head: String = head.into();
+        let pr_info = forge.fetch_pr(&head)?.map(|pr_info| PrInfo {
+            __createdAt: Some(SystemTime::now()),
+            ..pr_info
+        });
+        Ok(pr_info)
+    }
+
+    /// fetch the open pr that has `sha` as its head, or among its commits
+    pub fn get_by_sha(forge: &dyn Forge, sha: &str) -> Result<Option<PrInfo>, ForgeError> {
+        let pr_info = forge.fetch_pr_by_sha(sha)?.map(|pr_info| PrInfo {
+            __createdAt: Some(SystemTime::now()),
+            ..pr_info
+        });
+        Ok(pr_info)
+    }
+
+    /// fetch pr `number` from the forge
+    pub fn get_by_number(forge: &dyn Forge, number: u32) -> Result<Option<PrInfo>, ForgeError> {
+        let pr_info = forge.fetch_pr_by_number(number)?.map(|pr_info| PrInfo {
+            __createdAt: Some(SystemTime::now()),
+            ..pr_info
+        });
+        Ok(pr_info)
+    }
+
+    /// push the current branch to the push remote, showing the transfer on `progress`, and open a
+    /// pr for it on the upstream remote, the body starts from the commits and the repo's pr
+    /// template
+    pub fn create(
+        repo: &Repository,
+        forge: &dyn Forge,
+        options: &CreateOptions,
+        progress: &ProgressBar,
+    ) -> Result<PrInfo, Error> {
+        let current_branch_name = current_branch_name(repo).ok_or_else(|| {
+            Error::NotFound("HEAD is detached, check out the branch to open a pr for".to_string())
+        })?;
+        let (base, merge_base) = pr_base(repo, None, forge)?;
+        let commits = commits_since(repo, merge_base)?;
+        let (title, mut body) = title_and_body(repo, &current_branch_name, &commits);
+        if let Some(template) = template::pick(repo, options.template.as_deref())? {
+            body = match body.is_empty() {
+                true => template.trim().to_string(),
+                false => format!("{body}\n\n{}", template.trim()),
+            };
+        }
+        let mut reviewers = options.reviewers.clone();
+        if let (true, Some(codeowners)) = (
+            options.request_owners,
+            repo.workdir().and_then(CodeOwners::load),
+        ) {
+            let files = changed_files(repo, merge_base)?;
+            // the forge won't have the pr's own authors review it
+            let authors = authors(forge, &commits);
+            for owner in codeowners.owners_of(files.iter().map(String::as_str)) {
+                match codeowners::reviewer(&owner) {
+                    Some(reviewer)
+                        if !reviewers.iter().any(|r| r == reviewer)
+                            && !authors.iter().any(|a| a.eq_ignore_ascii_case(reviewer)) =>
+                    {
+                        reviewers.push(reviewer.to_string())
+                    }
+                    _ => {}
                 }
-                .to_string(),
-            )
-        } else {
-            match shell::run(cmd).ok() {
-                None => None,
-                Some(s) if s.stdout.is_empty() => None,
-                Some(s) => Some(s.stdout_str()),
+            }
+        }
+        let head = pr_head(repo, &current_branch_name);
+        let suggested = suggested_reviewers(repo, merge_base);
+        let (title, body) = match options.edit {
+            true => {
+                let mut note = formatdoc!(
+                    "
+                        The first line is the pr title and the rest is its body, an empty title
+                        aborts the pr. Everything from the line above down is left out.
+
+                        {head} -> {base}
+                    "
+                );
+                if !suggested.is_empty() {
+                    note.push_str(&format!(
+                        "\nsuggested reviewers:\n  {}\n",
+                        suggested.join("\n  ")
+                    ));
+                }
+                template::edit(repo, &template::message(&title, &body, &note))?
+            }
+            false => {
+                for suggestion in &suggested {
+                    info!("suggested reviewer: {suggestion}");
+                }
+                (title, body)
             }
         };
+        if title.trim().is_empty() {
+            return Err(template::TemplateError::EmptyTitle.into())
+        }
 
-        // debug!("{:?}", stdout.clone()?);
-        let pr_info = match from_str::<[PrInfo; 1]>(&stdout?) {
-            Ok([pr_info]) => PrInfo {
-                __createdAt: Some(SystemTime::now()),
-                ..pr_info
-            },
+        // pushed only once there is a pr to open, so backing out of the editor leaves the remote
+        // alone
+        let push_remote = config::push_remote(repo);
+        let lease = match options.force_with_lease {
+            true => remote_branch_oid(repo, &push_remote, &current_branch_name),
+            false => None,
+        };
+        push_branch(repo, &current_branch_name, lease, options.dry_run, progress)?;
+
+        let new_pr = NewPr {
+            title,
+            body,
+            head,
+            base,
+            draft: options.draft,
+            reviewers,
+            assignees: options.assignees.clone(),
+            labels: options.labels.clone(),
+            milestone: options.milestone.clone(),
+        };
+        let pr_info = forge.create_pr(&new_pr)?;
+        Ok(PrInfo {
+            __createdAt: Some(SystemTime::now()),
+            ..pr_info
+        })
+    }
+
+    /// whether what we have is `REFRESH_EVERY` old and should be fetched again
+    pub fn is_stale(&self) -> bool {
+        self.__createdAt
+            .and_then(|created_at| created_at.elapsed().ok())
+            .is_none_or(|age| age >= REFRESH_EVERY)
+    }
+
+    /// fetch pr `number` again, for `update` to take in, so what we have stays readable meanwhile
+    pub fn refetch(forge: &dyn Forge, number: u32) -> Result<PrInfo, Error> {
+        PrInfo::get_by_number(forge, number)?
+            .ok_or_else(|| Error::NoPr(format!("pr #{number} is gone")))
+    }
+
+    /// take in what `refetch` got, when that failed what we have is kept, marked with the error,
+    /// until the next try
+    pub fn update(&mut self, fetched: Result<PrInfo, Error>) -> Result<(), Error> {
+        match fetched {
+            Ok(pr_info) => {
+                *self = pr_info;
+                Ok(())
+            }
             Err(e) => {
-                debug!("error parsing pr info: {:?}", e);
-                return None
+                // a whole interval goes by before the next try
+                self.__createdAt = Some(SystemTime::now());
+                self.__refreshError = Some(e.to_string());
+                Err(e)
             }
-        };
-        return Some(pr_info)
+        }
     }
+}
 
-    /// use the gh cli tool to create a pr
-    pub fn create(repo: &Repository, draft: bool) -> Option<PrInfo> {
-        let _draft_arg = if draft { "--draft" } else { "" };
-        let _title = "";
-        let _body = "";
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
 
-        let current_branch_name = current_branch_name(repo).expect("must have current branch name");
-        info!("pushing remote origin {:?}", current_branch_name);
-        let _result = repo
-            .find_remote("origin")
-            .and_then(|mut remote| remote.push(&[current_branch_name.clone()], None));
+    use super::*;
+    use crate::prinfo::{CheckConclusionState, CheckStatusState, StatusContextState, User};
 
-        let main_branch = get_main_branch(repo).ok()?;
-        let merge_base = get_merge_base(repo, &main_branch);
-        let merge_base_commit = repo.find_commit(merge_base).ok()?;
+    fn run(
+        name: &str,
+        status: CheckStatusState,
+        conclusion: Option<CheckConclusionState>,
+    ) -> StatusCheck {
+        StatusCheck::CheckRun {
+            completedAt: String::new(),
+            conclusion,
+            detailsUrl: String::new(),
+            name: name.to_string(),
+            startedAt: String::new(),
+            status,
+            workflowName: String::new(),
+        }
+    }
 
-        let (title, body) = merge_base_commit.message()?.split_once('\n')?;
+    fn context(name: &str, state: StatusContextState) -> StatusCheck {
+        StatusCheck::StatusContext {
+            context: name.to_string(),
+            startedAt: String::new(),
+            state,
+            targetUrl: String::new(),
+        }
+    }
 
-        let draft_arg = match draft {
-            true => "--draft",
-            false => "",
-        };
+    fn done(name: &str, conclusion: CheckConclusionState) -> StatusCheck {
+        run(name, CheckStatusState::Completed, Some(conclusion))
+    }
 
-        let _result = shell::run(format!(
-            "gh pr create --title='{title}' --body='{body}' {draft_arg} "
-        ));
-        PrInfo::get(current_branch_name.clone())
+    fn with_checks(checks: Vec<StatusCheck>) -> PrInfo {
+        PrInfo {
+            statusCheckRollup: checks,
+            ..Default::default()
+        }
     }
 
-    pub fn update(&mut self) -> Self {
-        if SystemTime::now()
-            .duration_since(self.__createdAt.unwrap())
-            .unwrap()
-            .as_secs()
-            >= 15
-        {
-            let pr_info = PrInfo::get(&self.headRefName).expect("must have new info");
-            pr_info.clone_into(self);
-            // info!("updated pr info");
-        } else {
-            // info!("cached pr info");
+    #[test]
+    fn test_check_outcome() {
+        for (check, outcome) in [
+            (run("build", CheckStatusState::InProgress, None), CheckOutcome::Pending),
+            (run("build", CheckStatusState::Completed, None), CheckOutcome::Passed),
+            (done("build", CheckConclusionState::Success), CheckOutcome::Passed),
+            (done("build", CheckConclusionState::Skipped), CheckOutcome::Passed),
+            (done("build", CheckConclusionState::Failure), CheckOutcome::Failed),
+            (done("build", CheckConclusionState::ActionRequired), CheckOutcome::Failed),
+            (done("build", CheckConclusionState::TimedOut), CheckOutcome::Cancelled),
+            (context("ci", StatusContextState::Expected), CheckOutcome::Pending),
+            (context("ci", StatusContextState::Error), CheckOutcome::Failed),
+            (context("ci", StatusContextState::Success), CheckOutcome::Passed),
+        ] {
+            assert_eq!(check.outcome(), outcome, "{check:?}");
         }
-        self.clone()
+    }
+
+    #[test]
+    fn test_pr_outcome_is_the_worst() {
+        let pr_info = with_checks(vec![
+            done("test", CheckConclusionState::Success),
+            context("deploy", StatusContextState::Pending),
+        ]);
+        assert_eq!(pr_info.outcome(), CheckOutcome::Pending);
+        let pr_info = with_checks(vec![
+            done("test", CheckConclusionState::Failure),
+            done("lint", CheckConclusionState::Cancelled),
+            context("deploy", StatusContextState::Pending),
+        ]);
+        assert_eq!(pr_info.outcome(), CheckOutcome::Failed);
+    }
+
+    #[test]
+    fn test_failed_checks() {
+        let pr_info = with_checks(vec![
+            done("test", CheckConclusionState::Failure),
+            done("lint", CheckConclusionState::Success),
+            done("docs", CheckConclusionState::Failure),
+            run("build", CheckStatusState::InProgress, None),
+        ]);
+        let names = |required: &[String]| {
+            pr_info
+                .failed_checks(required)
+                .iter()
+                .map(|check| check.name())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(names(&[]), vec!["test", "docs"]);
+        assert_eq!(names(&["test".to_string(), "lint".to_string()]), vec!["test"]);
+        assert!(names(&["lint".to_string(), "build".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let mut pr_info = PrInfo {
+            number: 7,
+            __createdAt: Some(SystemTime::now()),
+            ..with_checks(vec![run("build", CheckStatusState::InProgress, None)])
+        };
+        assert!(!pr_info.is_stale());
+
+        // a failed refresh keeps what we have, marked, and waits a whole interval to try again
+        let failed = pr_info.update(Err(Error::NoPr("pr #7 is gone".to_string())));
+        assert!(failed.is_err());
+        assert_eq!(pr_info.number, 7);
+        assert_eq!(pr_info.__refreshError.as_deref(), Some("pr #7 is gone"));
+        assert!(!pr_info.is_stale());
+
+        let fetched = PrInfo {
+            number: 7,
+            ..with_checks(vec![done("build", CheckConclusionState::Success)])
+        };
+        pr_info.update(Ok(fetched)).unwrap();
+        assert!(pr_info.is_complete());
+        assert_eq!(pr_info.__refreshError, None);
+    }
+
+    #[test]
+    fn test_no_checks_yet() {
+        let pr_info = with_checks(vec![]);
+        assert!(!pr_info.is_complete());
+        assert_eq!(pr_info.outcome(), CheckOutcome::Pending);
+        let pr_info = with_checks(vec![done("test", CheckConclusionState::Success)]);
+        assert!(pr_info.is_complete());
+        assert_eq!(pr_info.outcome(), CheckOutcome::Passed);
+    }
+
+    #[test]
+    fn test_approvers() {
+        let review = |login: &str, state: &str| Review {
+            author: User {
+                login: login.to_string(),
+                ..Default::default()
+            },
+            state: state.to_string(),
+            ..Default::default()
+        };
+        let reviews = vec![
+            review("alice", "APPROVED"),
+            review("bob", "APPROVED"),
+            review("bob", "CHANGES_REQUESTED"),
+            review("carol", "CHANGES_REQUESTED"),
+            review("carol", "APPROVED"),
+            review("alice", "COMMENTED"),
+        ];
+        // gh leaves `latestReviews` out on servers that don't have it
+        let pr_info = PrInfo {
+            reviews: reviews.clone(),
+            ..Default::default()
+        };
+        assert_eq!(pr_info.approvers(), vec!["alice", "carol"]);
+        let pr_info = PrInfo {
+            reviews,
+            latestReviews: vec![review("bob", "APPROVED")],
+            ..Default::default()
+        };
+        assert_eq!(pr_info.approvers(), vec!["bob"]);
     }
 }
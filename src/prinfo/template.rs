@@ -0,0 +1,275 @@
+//! Pull request templates, and the editor the pr title and body are reviewed in before a pr is
+//! opened, much like `git commit` does for its message.
+use std::{
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, stdin, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use git2::Repository;
+
+/// where a repo keeps its single pr template, relative to the work tree
+const TEMPLATE_FILES: &[&str] = &[
+    ".github/pull_request_template.md",
+    "pull_request_template.md",
+    "docs/pull_request_template.md",
+];
+
+/// where a repo keeps a choice of pr templates
+const TEMPLATE_DIRS: &[&str] = &[
+    ".github/PULL_REQUEST_TEMPLATE",
+    "PULL_REQUEST_TEMPLATE",
+    "docs/PULL_REQUEST_TEMPLATE",
+    ".gitlab/merge_request_templates",
+];
+
+/// everything from this line down is left out of the pr, like `git commit --cleanup=scissors`
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+#[derive(Debug)]
+pub enum TemplateError {
+    /// reading a template or the edited message failed
+    Io(io::Error),
+    /// the editor exited with an error
+    Editor(String),
+    /// `--template` names a template the repo doesn't have
+    NotFound(String),
+    /// the title was left empty
+    EmptyTitle,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Io(e) => write!(f, "could not read the pr message: {e}"),
+            TemplateError::Editor(message) => write!(f, "editor failed: {message}"),
+            TemplateError::NotFound(name) => write!(f, "no pr template named {name}"),
+            TemplateError::EmptyTitle => write!(f, "aborting the pr due to an empty title"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+impl From<io::Error> for TemplateError {
+    fn from(e: io::Error) -> Self {
+        TemplateError::Io(e)
+    }
+}
+
+/// matches the file name case insensitively, as github does
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|file| file.eq_ignore_ascii_case(name))
+        })
+}
+
+/// the pr templates in `workdir`, the single template first, then the ones to choose from
+pub fn templates(workdir: &Path) -> Vec<PathBuf> {
+    let files = TEMPLATE_FILES.iter().filter_map(|file| {
+        let (dir, name) = file.rsplit_once('/').unwrap_or(("", file));
+        find_file(&workdir.join(dir), name)
+    });
+    let dirs = TEMPLATE_DIRS.iter().flat_map(|dir| {
+        let mut found = fs::read_dir(workdir.join(dir))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .collect::<Vec<PathBuf>>();
+        found.sort();
+        found
+    });
+    files.chain(dirs).collect()
+}
+
+fn template_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// ask which of `templates` to use on `output`, until `input` answers with one of them or none
+fn ask<'a>(
+    templates: &'a [PathBuf],
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Option<&'a PathBuf>> {
+    writeln!(output, "pr templates:")?;
+    for (i, path) in templates.iter().enumerate() {
+        writeln!(output, "  {}) {}", i + 1, template_name(path))?;
+    }
+    writeln!(output, "  0) none")?;
+    loop {
+        write!(output, "which one? [1] ")?;
+        output.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "no pr template was picked",
+            ))
+        }
+        match answer.trim() {
+            "" => return Ok(templates.first()),
+            "0" => return Ok(None),
+            answer => match answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| i.checked_sub(1))
+                .and_then(|i| templates.get(i))
+            {
+                Some(template) => return Ok(Some(template)),
+                None => writeln!(
+                    output,
+                    "{answer} isn't one of them, pick 0 to {}",
+                    templates.len()
+                )?,
+            },
+        }
+    }
+}
+
+/// the contents of the pr template to use, `name` picks one by file name or stem, otherwise a
+/// lone template is used as is and a choice of them is asked about
+pub fn pick(repo: &Repository, name: Option<&str>) -> Result<Option<String>, TemplateError> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(None)
+    };
+    let templates = templates(workdir);
+    let path = match name {
+        Some(name) => Some(
+            templates
+                .iter()
+                .find(|path| {
+                    template_name(path).eq_ignore_ascii_case(name)
+                        || path
+                            .file_name()
+                            .is_some_and(|file| file.eq_ignore_ascii_case(name))
+                })
+                .ok_or_else(|| TemplateError::NotFound(name.to_string()))?,
+        ),
+        None => match templates.as_slice() {
+            [] => None,
+            [template] => Some(template),
+            // the first when nobody is there to answer
+            _ if !stdin().is_terminal() => templates.first(),
+            _ => ask(&templates, &mut stdin().lock(), &mut io::stderr())?,
+        },
+    };
+    Ok(match path {
+        Some(path) => Some(fs::read_to_string(path)?),
+        None => None,
+    })
+}
+
+/// the editor git would use, `$GIT_EDITOR`, `core.editor`, `$VISUAL`, `$EDITOR`, then `vi`
+fn editor(repo: &Repository) -> String {
+    env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| repo.config().ok()?.get_string("core.editor").ok())
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+/// the message the editor is opened on, the title, then the body, then a note past the scissors
+pub fn message(title: &str, body: &str, note: &str) -> String {
+    let note = note
+        .lines()
+        .map(|line| format!("# {line}").trim_end().to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+    format!("{title}\n\n{body}\n\n{SCISSORS}\n{note}\n")
+}
+
+/// the title and body of an edited message, `None` when the title was left empty
+pub fn parse_message(message: &str) -> Option<(String, String)> {
+    let kept = message
+        .lines()
+        .take_while(|line| line.trim_end() != SCISSORS)
+        .collect::<Vec<&str>>()
+        .join("\n");
+    let kept = kept.trim_start();
+    let (title, body) = kept.split_once('\n').unwrap_or((kept, ""));
+    match title.trim() {
+        "" => None,
+        title => Some((title.to_string(), body.trim().to_string())),
+    }
+}
+
+/// open the editor on `message` in the git dir and return the title and body it was left with
+pub fn edit(repo: &Repository, message: &str) -> Result<(String, String), TemplateError> {
+    let path = repo.path().join("PR_EDITMSG");
+    fs::write(&path, message)?;
+    let editor = editor(repo);
+    // the editor is a shell snippet, e.g. `code --wait`, as it is for git
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(&editor)
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        return Err(TemplateError::Editor(format!(
+            "{editor} exited with {status}"
+        )))
+    }
+    parse_message(&fs::read_to_string(&path)?).ok_or(TemplateError::EmptyTitle)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let edited = message(
+            "Retry uploads",
+            "## Summary\n\n- Retry uploads\n- Add backoff",
+            "head: feature/retry-uploads",
+        );
+        assert_eq!(
+            parse_message(&edited),
+            Some((
+                "Retry uploads".to_string(),
+                "## Summary\n\n- Retry uploads\n- Add backoff".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_message("\n\nJust a title\n"),
+            Some(("Just a title".to_string(), String::new()))
+        );
+        assert_eq!(parse_message(&message("", "", "head: main")), None);
+    }
+
+    #[test]
+    fn test_ask_until_answered() {
+        let templates = vec![PathBuf::from("bug.md"), PathBuf::from("feature.md")];
+        let mut output = vec![];
+        let picked = ask(&templates, &mut "x\n3\n2\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(picked, Some(&templates[1]));
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("which one?").count(), 3, "{output}");
+        assert!(output.contains("3 isn't one of them, pick 0 to 2"), "{output}");
+
+        let none = ask(&templates, &mut "0\n".as_bytes(), &mut vec![]).unwrap();
+        assert_eq!(none, None);
+        let unanswered = ask(&templates, &mut "7\n".as_bytes(), &mut vec![]);
+        assert!(unanswered.is_err());
+    }
+}
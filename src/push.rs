@@ -0,0 +1,220 @@
+//! Pushing the pr branch with git2, authenticating the way git would and showing the transfer in
+//! the progress ui.
+use std::{env, path::PathBuf};
+
+use git2::{
+    Config, Cred, CredentialType, Direction, Error, Oid, PushOptions, RemoteCallbacks,
+    RemoteConnection, Repository,
+};
+use indicatif::{HumanBytes, ProgressBar};
+use log::debug;
+
+/// the key files ssh tries when there is no agent, in the order it tries them
+const SSH_KEYS: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// hands out each kind of credential once, libgit2 keeps asking for as long as we answer
+struct Credentials {
+    config: Config,
+    tried_agent: bool,
+    ssh_keys: Vec<PathBuf>,
+    tried_helper: bool,
+}
+
+impl Credentials {
+    fn new(repo: &Repository) -> Result<Self, Error> {
+        let ssh_dir = env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".ssh"))
+            .unwrap_or_default();
+        let mut ssh_keys = SSH_KEYS
+            .iter()
+            .map(|key| ssh_dir.join(key))
+            .filter(|key| key.is_file())
+            .collect::<Vec<PathBuf>>();
+        // popped off the end, so the first key goes last
+        ssh_keys.reverse();
+        Ok(Self {
+            config: repo.config()?,
+            tried_agent: false,
+            ssh_keys,
+            tried_helper: false,
+        })
+    }
+
+    fn next(
+        &mut self,
+        url: &str,
+        username: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, Error> {
+        let username = username.unwrap_or("git");
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username)
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !self.tried_agent && env::var_os("SSH_AUTH_SOCK").is_some() {
+                self.tried_agent = true;
+                debug!("trying the ssh agent for {url}");
+                return Cred::ssh_key_from_agent(username)
+            }
+            if let Some(key) = self.ssh_keys.pop() {
+                debug!("trying {} for {url}", key.display());
+                return Cred::ssh_key(username, None, &key, None)
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !self.tried_helper {
+            self.tried_helper = true;
+            debug!("trying the credential helper for {url}");
+            return Cred::credential_helper(&self.config, url, Some(username))
+        }
+        if allowed.contains(CredentialType::DEFAULT) {
+            return Cred::default()
+        }
+        Err(Error::from_str(&format!(
+            "no credentials left to try for {url}, check ssh-agent, ~/.ssh or `git credential`"
+        )))
+    }
+}
+
+/// check that the remote still has `branch` at `expected` before it is overwritten, like
+/// `git push --force-with-lease=<branch>:<expected>`, against the refs advertised on the
+/// connection the push goes out on, so it can't move in between
+fn check_lease(connection: &RemoteConnection, branch: &str, expected: Oid) -> Result<(), Error> {
+    let refname = format!("refs/heads/{branch}");
+    let actual = connection
+        .list()?
+        .iter()
+        .find(|head| head.name() == refname)
+        .map(|head| head.oid());
+    match actual {
+        Some(actual) if actual != expected => Err(Error::from_str(&format!(
+            "stale lease, {branch} is at {actual} on the remote rather than {expected}, fetch and \
+             check what changed"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// push `branch` to the branch of the same name on `remote` and make that its upstream, the
+/// transfer is shown on `progress`, with a `lease` the remote branch is overwritten as long as it
+/// is still at that commit
+pub fn push(
+    repo: &Repository,
+    remote: &str,
+    branch: &str,
+    lease: Option<Oid>,
+    progress: &ProgressBar,
+) -> Result<(), Error> {
+    let mut git_remote = repo.find_remote(remote)?;
+    let mut connect_credentials = Credentials::new(repo)?;
+    let mut connect_callbacks = RemoteCallbacks::new();
+    connect_callbacks.credentials(|url, username, allowed| {
+        connect_credentials.next(url, username, allowed)
+    });
+    // the push goes out on this connection, libgit2 reuses it rather than connecting again
+    let mut connection = git_remote.connect_auth(Direction::Push, Some(connect_callbacks), None)?;
+    if let Some(expected) = lease {
+        check_lease(&connection, branch, expected)?;
+    }
+
+    let mut credentials = Credentials::new(repo)?;
+    // the server can turn down a ref even when the push itself went through
+    let mut rejected = None;
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| credentials.next(url, username, allowed));
+    callbacks.push_transfer_progress(|current, total, bytes| {
+        progress.set_length(total as u64);
+        progress.set_position(current as u64);
+        progress.set_message(HumanBytes(bytes as u64).to_string());
+    });
+    callbacks.push_update_reference(|refname, status| {
+        if let Some(status) = status {
+            rejected = Some(format!("{refname} was rejected: {status}"));
+        }
+        Ok(())
+    });
+
+    let force = match lease {
+        Some(_) => "+",
+        None => "",
+    };
+    let refspec = format!("{force}refs/heads/{branch}:refs/heads/{branch}");
+    {
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        connection
+            .remote()
+            .push(&[refspec], Some(&mut options))?;
+    }
+    if let Some(rejected) = rejected {
+        return Err(Error::from_str(&rejected))
+    }
+
+    let mut config = repo.config()?;
+    config.set_str(&format!("branch.{branch}.remote"), remote)?;
+    config.set_str(
+        &format!("branch.{branch}.merge"),
+        &format!("refs/heads/{branch}"),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use git2::Signature;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn commit_on(repo: &Repository, branch: &str, message: &str) -> Oid {
+        let signature = Signature::now("Alice", "alice@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let refname = format!("refs/heads/{branch}");
+        let parent = repo
+            .refname_to_id(&refname)
+            .ok()
+            .map(|parent| repo.find_commit(parent).unwrap());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some(&refname),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_push_with_lease() {
+        let dir = env::temp_dir().join(format!("git-pr-push-{}", std::process::id()));
+        let origin = Repository::init_bare(dir.join("origin.git")).unwrap();
+        let repo = Repository::init(dir.join("clone")).unwrap();
+        repo.remote("origin", dir.join("origin.git").to_str().unwrap())
+            .unwrap();
+        let progress = ProgressBar::hidden();
+
+        let pushed = commit_on(&repo, "feature", "first");
+        push(&repo, "origin", "feature", None, &progress).unwrap();
+        assert_eq!(origin.refname_to_id("refs/heads/feature").unwrap(), pushed);
+
+        // someone else pushes to the branch, a lease on what we pushed is stale
+        let theirs = commit_on(&origin, "feature", "theirs");
+        let rewritten = commit_on(&repo, "feature", "second");
+        let stale = push(&repo, "origin", "feature", Some(pushed), &progress);
+        assert!(stale.unwrap_err().message().starts_with("stale lease"));
+        assert_eq!(origin.refname_to_id("refs/heads/feature").unwrap(), theirs);
+
+        push(&repo, "origin", "feature", Some(theirs), &progress).unwrap();
+        assert_eq!(
+            origin.refname_to_id("refs/heads/feature").unwrap(),
+            rewritten
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
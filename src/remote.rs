@@ -0,0 +1,263 @@
+//! Parsing remote urls into the host and repository they point at.
+use git2::Repository;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Ssh,
+    Git,
+    Http,
+    Https,
+}
+
+/// where a remote lives, e.g. `git@github.com:org/repo.git` is `github.com`, `org`, `repo`
+///
+/// `owner` is everything between the host and the repo, so nested gitlab groups come out as
+/// `group/subgroup`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteSpec {
+    pub host: String,
+    pub port: Option<u16>,
+    pub owner: String,
+    pub repo: String,
+    pub protocol: Protocol,
+}
+
+impl RemoteSpec {
+    /// the spec of the remote called `name`, after applying any `url.<base>.insteadOf` rewrites
+    pub fn of(repo: &Repository, name: &str) -> Option<Self> {
+        let config = repo.config().ok()?;
+        let url = config.get_string(&format!("remote.{name}.url")).ok()?;
+        Self::parse(&instead_of(&config, &url))
+    }
+
+    /// parse an scp-like (`user@host:path`), `ssh://`, `git://` or `http(s)://` url, local paths
+    /// and `file://` urls aren't on a forge so they are `None`
+    pub fn parse(url: &str) -> Option<Self> {
+        let (protocol, authority, path) = match url.split_once("://") {
+            Some((scheme, rest)) => {
+                let protocol = match scheme {
+                    "ssh" | "git+ssh" | "ssh+git" => Protocol::Ssh,
+                    "git" => Protocol::Git,
+                    "http" => Protocol::Http,
+                    "https" => Protocol::Https,
+                    _ => return None,
+                };
+                let (authority, path) = rest.split_once('/')?;
+                (protocol, authority, path)
+            }
+            None => {
+                // scp-like urls have no slash before the colon, otherwise it's a local path
+                let (authority, path) = url.split_once(':')?;
+                if authority.contains('/') {
+                    return None
+                }
+                (Protocol::Ssh, authority, path)
+            }
+        };
+
+        let host_port = authority.rsplit('@').next()?;
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse().ok()?)),
+            None => (host_port, None),
+        };
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let (owner, repo) = path.rsplit_once('/')?;
+        if host.is_empty() || owner.is_empty() || repo.is_empty() {
+            return None
+        }
+
+        Some(Self {
+            host: host.to_lowercase(),
+            port,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            protocol,
+        })
+    }
+
+    /// `owner/repo`
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.owner, self.repo)
+    }
+
+    /// whether `self` and `other` are the same repo reached different ways, e.g. over ssh and from
+    /// a pr's web url
+    pub fn same_repo(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+
+    /// the host, owner and repo without case or whatever only says how the repo is reached: an
+    /// `ssh.` host, bitbucket server's `scm/` and `projects/<project>/repos/`, azure devops' `v3/`
+    /// and `_git/`
+    fn key(&self) -> (String, String, String) {
+        let host = self.host.strip_prefix("ssh.").unwrap_or(&self.host);
+        let owner = self.owner.to_lowercase();
+        let owner = match owner.split('/').collect::<Vec<&str>>().as_slice() {
+            ["scm" | "v3", rest @ ..] => rest.to_vec(),
+            ["projects", project, "repos"] => vec![*project],
+            segments => segments.to_vec(),
+        }
+        .into_iter()
+        .filter(|segment| *segment != "_git")
+        .collect::<Vec<&str>>()
+        .join("/");
+        (host.to_string(), owner, self.repo.to_lowercase())
+    }
+
+    /// the host (and port) the web ui and api are served from, ssh ports have nothing to do with
+    /// it so they are dropped
+    pub fn web_host(&self) -> String {
+        match (self.protocol, self.port) {
+            (Protocol::Http | Protocol::Https, Some(port)) => format!("{}:{port}", self.host),
+            _ => self.host.clone(),
+        }
+    }
+}
+
+/// rewrite `url` like git does for `url.<base>.insteadOf <prefix>`, the longest prefix wins
+fn instead_of(config: &git2::Config, url: &str) -> String {
+    let mut best: Option<(String, String)> = None;
+    if let Ok(entries) = config.entries(Some(r"^url\..*\.insteadof$")) {
+        for entry in &entries {
+            let Ok(entry) = entry else { continue };
+            let (Some(name), Some(prefix)) = (entry.name(), entry.value()) else {
+                continue
+            };
+            let Some(base) = name
+                .strip_prefix("url.")
+                .and_then(|name| name.strip_suffix(".insteadof"))
+            else {
+                continue
+            };
+            let longer = match &best {
+                Some((_, best_prefix)) => prefix.len() > best_prefix.len(),
+                None => true,
+            };
+            if url.starts_with(prefix) && longer {
+                best = Some((base.to_string(), prefix.to_string()));
+            }
+        }
+    }
+    match best {
+        Some((base, prefix)) => format!("{base}{}", &url[prefix.len()..]),
+        None => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn spec(url: &str) -> Option<(String, Option<u16>, String, String, Protocol)> {
+        RemoteSpec::parse(url).map(|s| (s.host, s.port, s.owner, s.repo, s.protocol))
+    }
+
+    fn some(
+        host: &str,
+        port: Option<u16>,
+        owner: &str,
+        repo: &str,
+        protocol: Protocol,
+    ) -> Option<(String, Option<u16>, String, String, Protocol)> {
+        Some((
+            host.to_string(),
+            port,
+            owner.to_string(),
+            repo.to_string(),
+            protocol,
+        ))
+    }
+
+    #[test]
+    fn test_parse() {
+        use Protocol::*;
+        assert_eq!(
+            spec("git@github.com:org/repo.git"),
+            some("github.com", None, "org", "repo", Ssh)
+        );
+        assert_eq!(
+            spec("ssh://git@host.example.com:22/org/repo"),
+            some("host.example.com", Some(22), "org", "repo", Ssh)
+        );
+        assert_eq!(
+            spec("https://github.com/org/repo"),
+            some("github.com", None, "org", "repo", Https)
+        );
+        assert_eq!(
+            spec("https://user@gitlab.com/group/sub/repo.git/"),
+            some("gitlab.com", None, "group/sub", "repo", Https)
+        );
+        assert_eq!(
+            spec("http://git.example.com:3000/org/repo.git"),
+            some("git.example.com", Some(3000), "org", "repo", Http)
+        );
+        assert_eq!(
+            spec("git://GitHub.Example.com/org/repo"),
+            some("github.example.com", None, "org", "repo", Git)
+        );
+        assert_eq!(spec("/srv/git/repo.git"), None);
+        assert_eq!(spec("./org/repo"), None);
+        assert_eq!(spec("file:///srv/git/org/repo.git"), None);
+        assert_eq!(spec("git@github.com:repo.git"), None);
+    }
+
+    #[test]
+    fn test_web_host() {
+        let https = RemoteSpec::parse("https://git.example.com:8443/org/repo").unwrap();
+        assert_eq!(https.web_host(), "git.example.com:8443");
+        let ssh = RemoteSpec::parse("ssh://git@git.example.com:7999/org/repo").unwrap();
+        assert_eq!(ssh.web_host(), "git.example.com");
+    }
+
+    #[test]
+    fn test_same_repo() {
+        let same = |a: &str, b: &str| {
+            RemoteSpec::parse(a)
+                .unwrap()
+                .same_repo(&RemoteSpec::parse(b).unwrap())
+        };
+        assert!(same("git@github.com:Org/Repo.git", "https://github.com/org/repo"));
+        assert!(same(
+            "git@ssh.dev.azure.com:v3/org/project/repo",
+            "https://dev.azure.com/org/project/_git/repo"
+        ));
+        assert!(same(
+            "https://git.example.com/scm/proj/repo.git",
+            "https://git.example.com/projects/PROJ/repos/repo"
+        ));
+        assert!(!same("git@github.com:org/repo.git", "https://github.com/fork/repo"));
+        assert!(!same("git@github.com:org/repo.git", "https://ghe.example.com/org/repo"));
+    }
+
+    #[test]
+    fn test_instead_of() {
+        let dir = std::env::temp_dir().join(format!("git-pr-instead-of-{}", std::process::id()));
+        let repo = Repository::init(&dir).unwrap();
+        let mut config = repo.config().unwrap();
+        config
+            .set_str("url.git@github.com:.insteadOf", "gh:")
+            .unwrap();
+        config
+            .set_str("url.https://ghe.example.com/.insteadOf", "gh:corp/")
+            .unwrap();
+        config.set_str("remote.origin.url", "gh:org/repo").unwrap();
+        config
+            .set_str("remote.corp.url", "gh:corp/team/repo")
+            .unwrap();
+
+        let origin = RemoteSpec::of(&repo, "origin").unwrap();
+        assert_eq!(
+            (origin.host.as_str(), origin.path(), origin.protocol),
+            ("github.com", "org/repo".to_string(), Protocol::Ssh)
+        );
+        let corp = RemoteSpec::of(&repo, "corp").unwrap();
+        assert_eq!(
+            (corp.host.as_str(), corp.path(), corp.protocol),
+            ("ghe.example.com", "team/repo".to_string(), Protocol::Https)
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
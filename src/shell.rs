@@ -1,18 +1,222 @@
-use std::io::{stdout, Write};
-
-use subprocess::{CaptureData, Exec, Redirection};
-
-pub fn run<S: Into<String>>(cmd: S) -> Result<CaptureData, CaptureData> {
-    let cmd = cmd.into();
-    println!("running: {:#?}", cmd);
-    stdout().flush();
-    let _message = "doing work".to_string();
-    // let sp = Spinner::new(Spinners::Dots, message);
-    let ret = match Exec::shell(cmd).stdout(Redirection::Pipe).capture() {
-        Ok(v) if v.exit_status.success() => Ok(v),
-        Ok(v) => Err(v),
-        Err(_) => panic!("unknown exit_status"),
-    };
-    // sp.stop_with_symbol("💁‍♀️");
-    ret
+//! Running other programs, like `gh`, from an argv list rather than through a shell, so titles and
+//! bodies with quotes in them are passed along as they are.
+use std::{
+    env,
+    error::Error,
+    fmt,
+    io::{self, ErrorKind},
+    path::PathBuf,
+    time::Duration,
+};
+
+use log::debug;
+use subprocess::{Exec, ExitStatus, NullFile, PopenError, Redirection};
+
+/// how long a command may run before it is killed, unless it is given a timeout of its own
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum ShellError {
+    /// the program couldn't be started, e.g. it isn't installed
+    Spawn(String, PopenError),
+    /// the program ran for longer than its timeout and was killed
+    Timeout(String, Duration),
+    /// the program exited unsuccessfully, with what it wrote to stderr
+    Failed(String, ExitStatus, String),
+    /// reading the program's output, or waiting on it, failed
+    Io(String, io::Error),
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShellError::Spawn(cmd, e) => write!(f, "could not run {cmd}: {e}"),
+            ShellError::Timeout(cmd, timeout) => write!(f, "{cmd} timed out after {timeout:?}"),
+            ShellError::Failed(cmd, status, stderr) => {
+                match status {
+                    ExitStatus::Exited(code) => write!(f, "{cmd} exited with {code}")?,
+                    ExitStatus::Signaled(signal) => {
+                        write!(f, "{cmd} was killed by signal {signal}")?
+                    }
+                    status => write!(f, "{cmd} failed with {status:?}")?,
+                }
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {stderr}"),
+                }
+            }
+            ShellError::Io(cmd, e) => write!(f, "could not read the output of {cmd}: {e}"),
+        }
+    }
+}
+
+impl Error for ShellError {}
+
+/// what a command wrote
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// a command to run, built up from the program and its arguments
+#[derive(Debug, Clone)]
+pub struct Cmd {
+    program: String,
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    timeout: Duration,
+    /// values left out of logs and errors, e.g. tokens passed in the env
+    secrets: Vec<String>,
+}
+
+impl Cmd {
+    pub fn new<S: Into<String>>(program: S) -> Self {
+        Self {
+            program: program.into(),
+            args: vec![],
+            env: vec![],
+            timeout: DEFAULT_TIMEOUT,
+            secrets: vec![],
+        }
+    }
+
+    pub fn args<I: IntoIterator<Item = S>, S: Into<String>>(mut self, args: I) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env.push((key.into(), value.into()));
+        self
+    }
+
+    // nothing needs more, or less, than the default yet but the tests
+    #[allow(dead_code)]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// keep `secret` out of logs and errors
+    pub fn secret<S: Into<String>>(mut self, secret: S) -> Self {
+        let secret = secret.into();
+        if !secret.is_empty() {
+            self.secrets.push(secret);
+        }
+        self
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.secrets
+            .iter()
+            .fold(text.to_string(), |text, secret| text.replace(secret, "***"))
+    }
+
+    /// run the command, waiting at most its timeout, and capture what it writes
+    pub fn run(&self) -> Result<Output, ShellError> {
+        let cmd = self.to_string();
+        debug!("running {cmd}");
+        let mut popen = Exec::cmd(&self.program)
+            .args(&self.args)
+            .env_extend(&self.env)
+            // nobody is there to answer a prompt
+            .stdin(NullFile)
+            .stdout(Redirection::Pipe)
+            .stderr(Redirection::Pipe)
+            .popen()
+            .map_err(|e| ShellError::Spawn(cmd.clone(), e))?;
+
+        let captured = popen
+            .communicate_start(None)
+            .limit_time(self.timeout)
+            .read();
+        let (stdout, stderr) = match captured {
+            Ok(captured) => captured,
+            Err(e) => {
+                let _ = popen.kill();
+                let _ = popen.wait();
+                return Err(match e.kind() {
+                    ErrorKind::TimedOut => ShellError::Timeout(cmd, self.timeout),
+                    _ => ShellError::Io(cmd, e.error),
+                })
+            }
+        };
+        let status = popen.wait().map_err(|e| match e {
+            PopenError::IoError(e) => ShellError::Io(cmd.clone(), e),
+            e => ShellError::Io(cmd.clone(), io::Error::other(e)),
+        })?;
+
+        let output = Output {
+            stdout: String::from_utf8_lossy(&stdout.unwrap_or_default()).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr.unwrap_or_default()).into_owned(),
+        };
+        match status.success() {
+            true => Ok(output),
+            false => Err(ShellError::Failed(cmd, status, self.redact(&output.stderr))),
+        }
+    }
+}
+
+/// the command line, quoted where needed and with secrets left out, for logs and errors
+impl fmt::Display for Cmd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = std::iter::once(&self.program)
+            .chain(&self.args)
+            .map(|word| {
+                let word = self.redact(word);
+                match word.is_empty() || word.contains(|c: char| c.is_whitespace() || c == '\'') {
+                    true => format!("{word:?}"),
+                    false => word,
+                }
+            })
+            .collect::<Vec<String>>();
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// the full path of `binary` if it is somewhere on `$PATH`
+pub fn which(binary: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(binary))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let cmd = Cmd::new("sh")
+            .args([
+                "-c",
+                "echo \"$0 it's $T\"; echo \"token $T\" >&2; exit ${1:-0}",
+                "sh",
+            ])
+            .env("T", "s3cret")
+            .secret("s3cret");
+        assert_eq!(cmd.run().unwrap().stdout, "sh it's s3cret\n");
+
+        let err = cmd.clone().args(["3"]).run().unwrap_err();
+        assert!(matches!(
+            err,
+            ShellError::Failed(_, ExitStatus::Exited(3), _)
+        ));
+        assert!(
+            err.to_string().ends_with("exited with 3: token ***"),
+            "{err}"
+        );
+
+        let err = Cmd::new("sleep")
+            .args(["5"])
+            .timeout(Duration::from_millis(100))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, ShellError::Timeout(..)), "{err}");
+        assert!(matches!(
+            Cmd::new("no-such-program").run(),
+            Err(ShellError::Spawn(..))
+        ));
+    }
 }